@@ -0,0 +1,48 @@
+//! Times the render pass on synthetic wiring diagrams drawn with one stroke per segment, one
+//! per `DrawLines` command and batched.
+//!
+//! Run with `cargo bench --bench line_batching -- [SEGMENTS]`.
+
+use druid_frontend::ui::renderer::{self, SnapshotOptions};
+
+const DEFAULT_SEGMENTS: usize = 10_000;
+const FRAMES: usize = 20;
+
+fn main() {
+    // Cargo passes `--bench` along with the arguments after `--`.
+    let segments = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_SEGMENTS);
+
+    let options = SnapshotOptions {
+        frames: FRAMES,
+        ..SnapshotOptions::default()
+    };
+
+    let scenes = renderer::line_benchmark(segments, options).unwrap_or_else(|err| {
+        eprintln!("Can't run the benchmark: {}", err);
+        std::process::exit(1);
+    });
+
+    println!("{} segments, {} frames", segments, FRAMES);
+
+    for scene in &scenes {
+        println!("{}:", scene.scene);
+
+        let results = [
+            ("per segment", &scene.per_segment),
+            ("per command", &scene.per_command),
+            ("batched", &scene.batched),
+        ];
+
+        for (name, result) in &results {
+            println!(
+                "{:>12}: {:>6} draw calls, {:>8.3} ms per frame",
+                name,
+                result.draw_calls,
+                result.frame_time.as_secs_f64() * 1000.,
+            );
+        }
+    }
+}
@@ -1,15 +1,19 @@
 use druid::{
-    commands, AppDelegate, AppLauncher, Command, DelegateCtx, Env, Event, HotKey, KbKey,
-    LocalizedString, Target, Widget, WidgetExt, WindowDesc, WindowId,
+    commands, AppDelegate, AppLauncher, Command, DelegateCtx, Env, Event, FileDialogOptions,
+    FileSpec, HotKey, KbKey, LocalizedString, MenuDesc, MenuItem, Target, Widget, WidgetExt,
+    WindowDesc, WindowId,
 };
 
+use crate::args::{self, Args};
 use crate::theme;
 use crate::ui::debug;
-use crate::ui::scheme_editor;
+use crate::ui::renderer::{Recorder, Renderer, Replay, EXPORT_SVG};
+use crate::ui::scheme_editor::{self, SchemeEditorState};
 use crate::ui::ui_state::UiState;
 use crate::ui::widgets::Stack;
 
 const WINDOW_TITLE: LocalizedString<UiState> = LocalizedString::new("Tech.Paws");
+const SVG_FILE_TYPE: FileSpec = FileSpec::new("SVG image", &["svg"]);
 
 struct TechPawsAppDelegate {}
 
@@ -46,23 +50,109 @@ impl AppDelegate<UiState> for TechPawsAppDelegate {
             _ => Some(event),
         }
     }
+
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        _data: &mut UiState,
+        _env: &Env,
+    ) -> bool {
+        match cmd.get(commands::SAVE_FILE) {
+            Some(Some(file_info)) => {
+                ctx.submit_command(EXPORT_SVG.with(file_info.path().to_path_buf()));
+                false
+            }
+            _ => true,
+        }
+    }
 }
 
-pub fn build_ui() -> impl Widget<UiState> {
+fn build_menu() -> MenuDesc<UiState> {
+    let export_svg = MenuItem::new(
+        LocalizedString::new("paws-tech-menu-export-svg").with_placeholder("Export as SVG..."),
+        commands::SHOW_SAVE_PANEL.with(
+            FileDialogOptions::new()
+                .allowed_types(vec![SVG_FILE_TYPE])
+                .default_type(SVG_FILE_TYPE),
+        ),
+    );
+
+    let file_menu = MenuDesc::new(LocalizedString::new("common-menu-file-menu")).append(export_svg);
+
+    MenuDesc::platform_default()
+        .unwrap_or_else(MenuDesc::empty)
+        .append(file_menu)
+}
+
+pub fn build_ui(renderer: Renderer<SchemeEditorState>) -> impl Widget<UiState> {
     Stack::new()
-        .with_child(scheme_editor::build_ui().lens(UiState::scheme_editor))
+        .with_child(scheme_editor::build_ui(renderer).lens(UiState::scheme_editor))
         .with_child(debug::build_ui().lens(UiState::debug))
 }
 
+fn build_renderer(args: &Args) -> Result<Renderer<SchemeEditorState>, String> {
+    let mut renderer = Renderer::new()
+        .with_schedule(args.schedule)
+        .with_validation(args.validate || cfg!(debug_assertions));
+
+    if let Some(timestep) = args.timestep {
+        renderer = renderer.with_timestep(timestep);
+    }
+
+    if let Some(path) = &args.record {
+        let recorder = Recorder::create(path)
+            .map_err(|err| format!("Can't record to {}: {}", path.display(), err))?;
+        renderer = renderer.with_recorder(recorder);
+    }
+
+    if let Some(path) = &args.replay {
+        let replay = Replay::open(path)
+            .map_err(|err| format!("Can't replay {}: {}", path.display(), err))?;
+        renderer = renderer.with_replay(replay);
+    }
+
+    Ok(renderer)
+}
+
 pub fn run() {
-    let main_window = WindowDesc::new(build_ui)
+    let args = Args::from_env().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, args::USAGE);
+        std::process::exit(2);
+    });
+
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+
+    let mut renderer = build_renderer(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    if let Some(path) = &args.snapshot {
+        tech_paws_core::init_world();
+
+        if let Err(err) = renderer.save_snapshot(path, args.snapshot_options) {
+            eprintln!("Can't save the snapshot to {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let main_window = WindowDesc::new(move || build_ui(renderer))
         .title(WINDOW_TITLE)
+        .menu(build_menu())
         .window_size((1024.0, 700.0));
 
     tech_paws_core::init_world();
 
     AppLauncher::with_window(main_window)
         .delegate(TechPawsAppDelegate::new())
+        .use_simple_logger()
         .configure_env(|env, _| theme::init(env))
         .launch(UiState::new())
         .expect("Failed to launch application");
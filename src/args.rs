@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use druid::Size;
+
+use crate::ui::renderer::{FrameSchedule, SnapshotOptions};
+
+pub const USAGE: &str = "\
+Usage: druid-frontend [OPTIONS]
+
+Options:
+    --record <FILE>    Record the render command stream of every frame into FILE
+    --replay <FILE>    Play a recorded render command stream instead of running the core
+    --snapshot <FILE>  Render the canvas into a PNG file without opening a window
+    --size <WxH>       Viewport size of the snapshot, 1024x700 by default
+    --scale <SCALE>    Pixels per device independent pixel of the snapshot, 1 by default
+    --frames <COUNT>   Number of frames to run before taking the snapshot, 1 by default
+    --fps <FPS>        Maximum number of frames painted per second, 60 by default
+    --always-redraw    Repaint on every frame even when nothing changes
+    --timestep <MS>    Duration of a simulation step in milliseconds, 16.67 by default
+    --validate         Log malformed render commands, on by default in debug builds
+    --help             Print this message";
+
+/// Command line arguments of the application.
+#[derive(Default)]
+pub struct Args {
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
+    pub snapshot_options: SnapshotOptions,
+    pub schedule: FrameSchedule,
+    pub timestep: Option<Duration>,
+    pub validate: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn from_env() -> Result<Self, String> {
+        Args::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut result = Args::default();
+        let mut args = args.into_iter();
+        let mut fps_cap = result.schedule.fps_cap();
+        let mut always_redraw = result.schedule.always_redraw();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => result.record = Some(path_value(&arg, args.next())?),
+                "--replay" => result.replay = Some(path_value(&arg, args.next())?),
+                "--snapshot" => result.snapshot = Some(path_value(&arg, args.next())?),
+                "--size" => result.snapshot_options.size = size_value(&arg, args.next())?,
+                "--scale" => result.snapshot_options.scale = number_value(&arg, args.next())?,
+                "--frames" => result.snapshot_options.frames = number_value(&arg, args.next())?,
+                "--fps" => fps_cap = number_value(&arg, args.next())?,
+                "--always-redraw" => always_redraw = true,
+                "--timestep" => result.timestep = Some(timestep_value(&arg, args.next())?),
+                "--validate" => result.validate = true,
+                "--help" | "-h" => result.help = true,
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+
+        if result.record.is_some() && result.replay.is_some() {
+            return Err("--record and --replay can't be used together".into());
+        }
+
+        if result.snapshot_options.scale <= 0. {
+            return Err("--scale must be positive".into());
+        }
+
+        result.schedule = FrameSchedule::new(fps_cap, always_redraw)
+            .ok_or_else(|| "--fps must be a positive number".to_string())?;
+
+        Ok(result)
+    }
+}
+
+fn path_value(arg: &str, value: Option<String>) -> Result<PathBuf, String> {
+    value
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("Missing file for {}", arg))
+}
+
+fn number_value<N: FromStr>(arg: &str, value: Option<String>) -> Result<N, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Expected a number for {}", arg))
+}
+
+fn timestep_value(arg: &str, value: Option<String>) -> Result<Duration, String> {
+    let millis: f64 = number_value(arg, value)?;
+
+    // Rejects steps rounding to zero nanoseconds too.
+    if !(millis >= 1e-6 && millis.is_finite()) {
+        return Err(format!("{} must be a positive number", arg));
+    }
+
+    Ok(Duration::from_secs_f64(millis / 1000.))
+}
+
+fn size_value(arg: &str, value: Option<String>) -> Result<Size, String> {
+    let error = || format!("Expected <width>x<height> of positive numbers for {}", arg);
+    let value = value.ok_or_else(error)?;
+    let mut parts = value.splitn(2, 'x');
+    let is_valid = |length: &f64| *length > 0. && length.is_finite();
+
+    let width: f64 = parts
+        .next()
+        .and_then(|width| width.parse().ok())
+        .filter(is_valid)
+        .ok_or_else(error)?;
+    let height: f64 = parts
+        .next()
+        .and_then(|height| height.parse().ok())
+        .filter(is_valid)
+        .ok_or_else(error)?;
+
+    Ok(Size::new(width, height))
+}
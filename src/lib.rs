@@ -0,0 +1,4 @@
+pub mod application;
+mod args;
+pub mod theme;
+pub mod ui;
@@ -1,7 +1,3 @@
-mod application;
-mod theme;
-mod ui;
-
 pub fn main() {
-    application::run();
+    druid_frontend::application::run();
 }
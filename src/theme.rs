@@ -28,6 +28,16 @@ pub const TERMINAL_TEXT_BOX_SELECTION_COLOR: Key<Color> =
 pub const TERMINAL_TEXT_BOX_SELECTION_TEXT_COLOR: Key<Color> =
     Key::new("paws.tech.terminal_text_box_selection_text_color");
 
+pub const CANVAS_BACKGROUND_COLOR: Key<Color> = Key::new("paws.tech.canvas_background_color");
+/// The bottom color of a vertical gradient, the same as the top one for a solid background.
+pub const CANVAS_BACKGROUND_END_COLOR: Key<Color> =
+    Key::new("paws.tech.canvas_background_end_color");
+pub const CANVAS_CORNER_RADIUS: Key<f64> = Key::new("paws.tech.canvas_corner_radius");
+pub const CANVAS_BORDER_COLOR: Key<Color> = Key::new("paws.tech.canvas_border_color");
+pub const CANVAS_BORDER_WIDTH: Key<f64> = Key::new("paws.tech.canvas_border_width");
+pub const CANVAS_GRID_COLOR: Key<Color> = Key::new("paws.tech.canvas_grid_color");
+pub const CANVAS_GRID_MAJOR_COLOR: Key<Color> = Key::new("paws.tech.canvas_grid_major_color");
+
 // pub const TEXT_BOX_TEXT_COLOR: Key<Color> = Key::new("paws.tech.button_click_text_color");
 pub const FOCUS_BORDER_COLOR: Key<Color> = Key::new("paws.tech.focus_border_color");
 
@@ -67,4 +77,15 @@ pub fn init(env: &mut Env) {
     );
     env.set(TERMINAL_TEXT_BOX_SELECTION_COLOR, Color::rgb8(0xFF, 0xFF, 0xFF));
     env.set(TERMINAL_TEXT_BOX_SELECTION_TEXT_COLOR, Color::rgb8(0x00, 0x00, 0x00));
+
+    env.set(CANVAS_BACKGROUND_COLOR, Color::rgb8(0x2B, 0x2B, 0x2B));
+    env.set(CANVAS_BACKGROUND_END_COLOR, Color::rgb8(0x2B, 0x2B, 0x2B));
+    env.set(CANVAS_CORNER_RADIUS, 4.0);
+    env.set(CANVAS_BORDER_COLOR, Color::rgba8(0x00, 0x00, 0x00, 0x00));
+    env.set(CANVAS_BORDER_WIDTH, 0.0);
+    env.set(CANVAS_GRID_COLOR, Color::rgba8(0xFF, 0xFF, 0xFF, 0x10));
+    env.set(
+        CANVAS_GRID_MAJOR_COLOR,
+        Color::rgba8(0xFF, 0xFF, 0xFF, 0x28),
+    );
 }
@@ -1,7 +1,11 @@
-use druid::widget::{Container, Flex, Focus, FocusScope, SizedBox};
-use druid::{Color, Data, Lens, Widget, WidgetExt, WidgetId};
+use std::path::PathBuf;
+
+use druid::widget::prelude::*;
+use druid::widget::{Container, Controller, Flex, Focus, FocusScope, SizedBox};
+use druid::{Color, Data, HotKey, KbKey, Lens, WidgetExt, WidgetId};
 
 use crate::ui::kit::TerminalTextboxDecorator;
+use crate::ui::renderer::EXPORT_SVG;
 use crate::ui::widgets::{AccessorDecorator, EditableText, Either};
 
 pub const TERMINAL_WIDGET_ID: WidgetId = WidgetId::reserved(1);
@@ -61,4 +65,43 @@ fn build_command_input() -> impl Widget<DebugState> {
         .with_id(TERMINAL_WIDGET_ID),
     )
     .padding(2.0)
+    .controller(TerminalController)
+}
+
+/// Runs the terminal command when Enter is pressed.
+struct TerminalController;
+
+impl<W: Widget<DebugState>> Controller<DebugState, W> for TerminalController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut DebugState,
+        env: &Env,
+    ) {
+        match event {
+            Event::KeyDown(key_event) if HotKey::new(None, KbKey::Enter).matches(key_event) => {
+                execute_command(ctx, &data.terminal_command);
+                data.terminal_command = "".into();
+                ctx.set_handled();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
+
+fn execute_command(ctx: &mut EventCtx, command: &str) {
+    let mut words = command.trim().splitn(2, ' ');
+    let name = words.next().unwrap_or("");
+    let args = words.next().unwrap_or("").trim();
+
+    match name {
+        "" => (),
+        "export-svg" if !args.is_empty() => {
+            ctx.submit_command(EXPORT_SVG.with(PathBuf::from(args)));
+        }
+        "export-svg" => log::error!("Usage: export-svg <path>"),
+        _ => log::error!("Unknown command: {}", name),
+    }
 }
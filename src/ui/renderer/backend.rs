@@ -0,0 +1,438 @@
+use std::iter;
+
+use druid::kurbo::{Affine, Circle, Shape};
+use druid::piet::{
+    self, FixedLinearGradient, FontWeight, GradientStop, ImageFormat, InterpolationMode,
+    RenderContext, Text, TextAlignment, TextAttribute, TextLayout, TextLayoutBuilder,
+};
+use druid::{Color, Point, Rect};
+
+use super::images::{Image, ImageCache};
+use super::layout_cache::{LayoutCache, LayoutKey};
+use super::stroke::Stroke;
+use super::text::{FontFamily, HorizontalAlignment, TextMetrics, TextStyle};
+
+/// A target for the primitives produced by the render commands of the core.
+///
+/// The command interpretation lives in [`Interpreter`](super::Interpreter); a backend only
+/// knows how to draw and measure already resolved primitives, so the same command stream
+/// can be sent to the window, headless and export targets.
+pub trait RenderBackend {
+    /// Fills an axis-aligned rectangle.
+    fn fill_quad(&mut self, rect: Rect, color: &Color);
+
+    /// Fills a circle.
+    fn fill_circle(&mut self, circle: Circle, color: &Color);
+
+    /// Strokes the outline of any shape: paths, arcs, ellipses and rounded rectangles.
+    fn stroke_shape(&mut self, shape: &impl Shape, color: &Color, stroke: &Stroke);
+
+    /// Fills any closed shape.
+    fn fill_shape(&mut self, shape: &impl Shape, color: &Color);
+
+    /// Fills a shape with a linear gradient between two points and their colors.
+    fn fill_linear_gradient(
+        &mut self,
+        shape: &impl Shape,
+        start: (Point, &Color),
+        end: (Point, &Color),
+    );
+
+    /// Draws the `source` part of the image, or all of it, scaled into `dest`.
+    ///
+    /// `opacity` goes from 0 for invisible to 1 for opaque.
+    fn draw_image(&mut self, image: &Image, dest: Rect, source: Option<Rect>, opacity: f64);
+
+    /// Drops everything the backend cached for the image.
+    fn release_image(&mut self, image: &Image);
+
+    /// Draws a single string with the alignment point of the style at `position`.
+    fn draw_text(&mut self, text: &str, position: Point, style: &TextStyle, color: &Color);
+
+    /// Measures the box `draw_text` would occupy for the string.
+    ///
+    /// `color` is the color the string is going to be drawn with, so backends baking the color
+    /// into their layouts can reuse the measured layout for drawing.
+    fn measure_text(&mut self, text: &str, style: &TextStyle, color: &Color) -> TextMetrics;
+
+    /// Saves the current clip and transform.
+    fn save(&mut self);
+
+    /// Restores the clip and transform to the last saved state.
+    fn restore(&mut self);
+
+    /// Intersects the current clip with the shape.
+    fn clip(&mut self, shape: &impl Shape);
+
+    /// Concatenates the transform to the current one.
+    fn transform(&mut self, transform: Affine);
+}
+
+/// A backend that draws through piet, used for the window and bitmap targets.
+///
+/// Text layouts and images are taken from caches that outlive the backend, so labels are
+/// only shaped and images only uploaded when they change.
+pub struct PietBackend<'a, R: RenderContext> {
+    ctx: &'a mut R,
+    layouts: &'a mut LayoutCache<R::TextLayout>,
+    images: &'a mut ImageCache<R::Image>,
+}
+
+impl<'a, R: RenderContext> PietBackend<'a, R> {
+    pub fn new(
+        ctx: &'a mut R,
+        layouts: &'a mut LayoutCache<R::TextLayout>,
+        images: &'a mut ImageCache<R::Image>,
+    ) -> Self {
+        PietBackend {
+            ctx,
+            layouts,
+            images,
+        }
+    }
+}
+
+/// A string shaped to read the line metrics of a font, its glyphs don't matter.
+const FONT_METRICS_SAMPLE: &str = "x";
+
+/// Returns the cached layout of the string, shaping it on a miss.
+fn cached_layout<'c, R: RenderContext>(
+    ctx: &mut R,
+    layouts: &'c mut LayoutCache<R::TextLayout>,
+    text: &str,
+    style: &TextStyle,
+    color: &Color,
+) -> Option<(&'c R::TextLayout, &'c TextMetrics)> {
+    layouts.get_or_build(LayoutKey::new(text, style), color, || {
+        let layout = build_layout(ctx, text, style, color)?;
+        let metrics = text_metrics(&layout);
+
+        Some((layout, metrics))
+    })
+}
+
+/// The metrics needed to draw the layout; the rest is added by `complete_metrics` when the
+/// string is measured.
+fn text_metrics<L: TextLayout>(layout: &L) -> TextMetrics {
+    let baseline = layout
+        .line_metric(0)
+        .map(|line| line.baseline)
+        .unwrap_or(0.);
+
+    TextMetrics {
+        size: layout.size(),
+        baseline,
+        line_count: layout.line_count(),
+        ..TextMetrics::default()
+    }
+}
+
+/// Adds the ascent and descent of the font and the carets, a hit test per char, to the
+/// metrics of the layout.
+fn complete_metrics<R: RenderContext>(
+    ctx: &mut R,
+    layout: &R::TextLayout,
+    text: &str,
+    style: &TextStyle,
+    metrics: &mut TextMetrics,
+) {
+    // Piet has no font metrics, but the line of an unwrapped layout has the ascent and descent
+    // of its font whatever the text is.
+    let sample_style = TextStyle {
+        max_width: None,
+        ..style.clone()
+    };
+    let font_line = build_layout(ctx, FONT_METRICS_SAMPLE, &sample_style, &Color::BLACK)
+        .and_then(|sample| sample.line_metric(0));
+
+    if let Some(line) = font_line {
+        metrics.ascent = line.baseline;
+        metrics.descent = line.height - line.baseline;
+    }
+
+    metrics.carets = text
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain(iter::once(text.len()))
+        .map(|index| caret(layout, index))
+        .collect();
+}
+
+/// Returns the top of the caret in front of the byte index.
+fn caret<L: TextLayout>(layout: &L, index: usize) -> Point {
+    layout
+        .hit_test_text_position(index)
+        .map(|hit| {
+            let top = layout
+                .line_metric(hit.line)
+                .map(|line| line.y_offset)
+                .unwrap_or(0.);
+            Point::new(hit.point.x, top)
+        })
+        .unwrap_or(Point::ORIGIN)
+}
+
+fn build_layout<R: RenderContext>(
+    ctx: &mut R,
+    text: &str,
+    style: &TextStyle,
+    color: &Color,
+) -> Option<R::TextLayout> {
+    let factory = ctx.text();
+    let family = match &style.family {
+        FontFamily::SystemUi => piet::FontFamily::SYSTEM_UI,
+        FontFamily::SansSerif => piet::FontFamily::SANS_SERIF,
+        FontFamily::Serif => piet::FontFamily::SERIF,
+        FontFamily::Monospace => piet::FontFamily::MONOSPACE,
+        FontFamily::Named(name) => {
+            factory.font_family(name).unwrap_or_else(|| {
+                log::warn!("Font family {:?} is not available", name);
+                piet::FontFamily::SYSTEM_UI
+            })
+        }
+    };
+
+    let alignment = match style.horizontal_alignment {
+        HorizontalAlignment::Left => TextAlignment::Start,
+        HorizontalAlignment::Center => TextAlignment::Center,
+        HorizontalAlignment::Right => TextAlignment::End,
+    };
+
+    let mut builder = factory
+        .new_text_layout(text)
+        .font(family, style.size)
+        .alignment(alignment)
+        .default_attribute(TextAttribute::Weight(FontWeight::new(style.weight)))
+        .default_attribute(TextAttribute::Italic(style.italic))
+        .default_attribute(TextAttribute::ForegroundColor(color.clone()));
+
+    if let Some(max_width) = style.max_width {
+        builder = builder.max_width(max_width);
+    }
+
+    let layout = builder.build();
+
+    match layout {
+        Ok(layout) => Some(layout),
+        Err(err) => {
+            log::error!("Failed to build text layout: {}", err);
+            None
+        }
+    }
+}
+
+impl<'a, R: RenderContext> RenderBackend for PietBackend<'a, R> {
+    fn fill_quad(&mut self, rect: Rect, color: &Color) {
+        self.ctx.fill(rect, color);
+    }
+
+    fn fill_circle(&mut self, circle: Circle, color: &Color) {
+        self.ctx.fill(circle, color);
+    }
+
+    fn stroke_shape(&mut self, shape: &impl Shape, color: &Color, stroke: &Stroke) {
+        if stroke == &Stroke::default() {
+            self.ctx.stroke(shape, color, stroke.width);
+        }
+        else {
+            self.ctx
+                .stroke_styled(shape, color, stroke.width, &stroke.style());
+        }
+    }
+
+    fn fill_shape(&mut self, shape: &impl Shape, color: &Color) {
+        self.ctx.fill(shape, color);
+    }
+
+    fn fill_linear_gradient(
+        &mut self,
+        shape: &impl Shape,
+        start: (Point, &Color),
+        end: (Point, &Color),
+    ) {
+        let gradient = FixedLinearGradient {
+            start: start.0,
+            end: end.0,
+            stops: vec![
+                GradientStop {
+                    pos: 0.,
+                    color: start.1.clone(),
+                },
+                GradientStop {
+                    pos: 1.,
+                    color: end.1.clone(),
+                },
+            ],
+        };
+
+        match self.ctx.gradient(gradient) {
+            Ok(brush) => self.ctx.fill(shape, &brush),
+            Err(err) => log::error!("Failed to make a gradient: {}", err),
+        }
+    }
+
+    fn draw_image(&mut self, image: &Image, dest: Rect, source: Option<Rect>, opacity: f64) {
+        let PietBackend { ctx, images, .. } = self;
+        let opacity = (opacity.max(0.).min(1.) * 255.).round() as u8;
+
+        let copy = images.get_or_make(image, opacity, |pixels| {
+            ctx.make_image(
+                image.width,
+                image.height,
+                &pixels,
+                ImageFormat::RgbaSeparate,
+            )
+            .map_err(|err| log::error!("Failed to upload an image: {}", err))
+            .ok()
+        });
+
+        if let Some(copy) = copy {
+            match source {
+                Some(source) => {
+                    ctx.draw_image_area(copy, source, dest, InterpolationMode::Bilinear)
+                }
+                None => ctx.draw_image(copy, dest, InterpolationMode::Bilinear),
+            }
+        }
+    }
+
+    fn release_image(&mut self, image: &Image) {
+        self.images.release(image);
+    }
+
+    fn draw_text(&mut self, text: &str, position: Point, style: &TextStyle, color: &Color) {
+        let PietBackend { ctx, layouts, .. } = self;
+
+        if let Some((layout, metrics)) = cached_layout(&mut **ctx, layouts, text, style, color) {
+            ctx.draw_text(
+                layout,
+                style.origin(position, metrics.size, metrics.baseline),
+            );
+        }
+    }
+
+    fn measure_text(&mut self, text: &str, style: &TextStyle, color: &Color) -> TextMetrics {
+        let PietBackend { ctx, layouts, .. } = self;
+        let key = LayoutKey::new(text, style);
+
+        if layouts.metrics(&key).is_none() {
+            // Shaped in the color it's drawn with, so the render pass reuses the layout.
+            cached_layout(&mut **ctx, layouts, text, style, color);
+        }
+
+        match layouts.get_mut(&key) {
+            Some((layout, metrics)) => {
+                // Every measured string has a caret, the strings that are only drawn have none.
+                if metrics.carets.is_empty() {
+                    complete_metrics(&mut **ctx, layout, text, style, metrics);
+                }
+
+                metrics.clone()
+            }
+            None => TextMetrics::default(),
+        }
+    }
+
+    fn save(&mut self) {
+        if let Err(err) = self.ctx.save() {
+            log::error!("Failed to save render state: {}", err);
+        }
+    }
+
+    fn restore(&mut self) {
+        if let Err(err) = self.ctx.restore() {
+            log::error!("Failed to restore render state: {}", err);
+        }
+    }
+
+    fn clip(&mut self, shape: &impl Shape) {
+        self.ctx.clip(shape);
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.ctx.transform(transform);
+    }
+}
+
+/// Sends every primitive to two backends, measuring text with the primary one.
+pub struct TeeBackend<'a, A: RenderBackend, B: RenderBackend> {
+    primary: &'a mut A,
+    secondary: &'a mut B,
+}
+
+impl<'a, A: RenderBackend, B: RenderBackend> TeeBackend<'a, A, B> {
+    pub fn new(primary: &'a mut A, secondary: &'a mut B) -> Self {
+        TeeBackend { primary, secondary }
+    }
+}
+
+impl<'a, A: RenderBackend, B: RenderBackend> RenderBackend for TeeBackend<'a, A, B> {
+    fn fill_quad(&mut self, rect: Rect, color: &Color) {
+        self.primary.fill_quad(rect, color);
+        self.secondary.fill_quad(rect, color);
+    }
+
+    fn fill_circle(&mut self, circle: Circle, color: &Color) {
+        self.primary.fill_circle(circle, color);
+        self.secondary.fill_circle(circle, color);
+    }
+
+    fn stroke_shape(&mut self, shape: &impl Shape, color: &Color, stroke: &Stroke) {
+        self.primary.stroke_shape(shape, color, stroke);
+        self.secondary.stroke_shape(shape, color, stroke);
+    }
+
+    fn fill_shape(&mut self, shape: &impl Shape, color: &Color) {
+        self.primary.fill_shape(shape, color);
+        self.secondary.fill_shape(shape, color);
+    }
+
+    fn fill_linear_gradient(
+        &mut self,
+        shape: &impl Shape,
+        start: (Point, &Color),
+        end: (Point, &Color),
+    ) {
+        self.primary.fill_linear_gradient(shape, start, end);
+        self.secondary.fill_linear_gradient(shape, start, end);
+    }
+
+    fn draw_image(&mut self, image: &Image, dest: Rect, source: Option<Rect>, opacity: f64) {
+        self.primary.draw_image(image, dest, source, opacity);
+        self.secondary.draw_image(image, dest, source, opacity);
+    }
+
+    fn release_image(&mut self, image: &Image) {
+        self.primary.release_image(image);
+        self.secondary.release_image(image);
+    }
+
+    fn draw_text(&mut self, text: &str, position: Point, style: &TextStyle, color: &Color) {
+        self.primary.draw_text(text, position, style, color);
+        self.secondary.draw_text(text, position, style, color);
+    }
+
+    fn measure_text(&mut self, text: &str, style: &TextStyle, color: &Color) -> TextMetrics {
+        self.primary.measure_text(text, style, color)
+    }
+
+    fn save(&mut self) {
+        self.primary.save();
+        self.secondary.save();
+    }
+
+    fn restore(&mut self) {
+        self.primary.restore();
+        self.secondary.restore();
+    }
+
+    fn clip(&mut self, shape: &impl Shape) {
+        self.primary.clip(shape);
+        self.secondary.clip(shape);
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.primary.transform(transform);
+        self.secondary.transform(transform);
+    }
+}
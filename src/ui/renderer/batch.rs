@@ -0,0 +1,83 @@
+//! Merging of the lines and quads of consecutive draw commands into single paths.
+
+use druid::kurbo::{Affine, BezPath, Line};
+use druid::{Color, Rect};
+
+use super::backend::RenderBackend;
+use super::stroke::Stroke;
+
+/// How the path of a batch is painted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchKind {
+    Lines(Stroke),
+    Quads,
+}
+
+/// Primitives sharing their paint, color and transform, drawn with a single backend call.
+///
+/// Overlapping primitives of a batch are painted once. That is invisible for opaque colors,
+/// but translucent primitives of separate commands would no longer darken where they cross,
+/// so only opaque batches may span several commands.
+pub struct Batch {
+    kind: BatchKind,
+    color: u32,
+    transform: Affine,
+    path: BezPath,
+}
+
+impl Batch {
+    pub fn new(kind: BatchKind, color: &Color, transform: Affine) -> Self {
+        Batch {
+            kind,
+            color: color.as_rgba_u32(),
+            transform,
+            path: BezPath::new(),
+        }
+    }
+
+    /// Whether primitives with the paint, color and transform can be added to the batch.
+    pub fn accepts(&self, kind: &BatchKind, color: &Color, transform: Affine) -> bool {
+        self.kind == *kind && self.color == color.as_rgba_u32() && self.transform == transform
+    }
+
+    pub fn add_line(&mut self, line: Line) {
+        self.path.move_to(line.p0);
+        self.path.line_to(line.p1);
+    }
+
+    pub fn add_rect(&mut self, rect: Rect) {
+        self.path.move_to((rect.x0, rect.y0));
+        self.path.line_to((rect.x1, rect.y0));
+        self.path.line_to((rect.x1, rect.y1));
+        self.path.line_to((rect.x0, rect.y1));
+        self.path.close_path();
+    }
+
+    /// Whether the batch can be merged with the primitives of the following commands without
+    /// changing the output.
+    pub fn is_opaque(&self) -> bool {
+        self.color & 0xFF == 0xFF
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.elements().is_empty()
+    }
+
+    pub fn draw<B: RenderBackend>(&self, backend: &mut B) {
+        if self.is_empty() {
+            return;
+        }
+
+        let color = Color::from_rgba32_u32(self.color);
+
+        backend.save();
+        backend.transform(self.transform);
+
+        match &self.kind {
+            BatchKind::Lines(stroke) => backend.stroke_shape(&self.path, &color, stroke),
+            BatchKind::Quads => backend.fill_shape(&self.path, &color),
+        }
+
+        backend.restore();
+    }
+}
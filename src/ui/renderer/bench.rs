@@ -0,0 +1,282 @@
+//! A synthetic scene for measuring the render pass without the core.
+
+use std::time::{Duration, Instant};
+
+use druid::kurbo::{Affine, Circle, Shape};
+use druid::piet::{self, Device, RenderContext};
+use druid::{Color, Point, Rect};
+
+use tech_paws_core as core;
+
+use super::backend::{PietBackend, RenderBackend};
+use super::commands::RenderOp;
+use super::headless::SnapshotOptions;
+use super::images::{Image, ImageCache};
+use super::interpreter::Interpreter;
+use super::layout_cache::LayoutCache;
+use super::stroke::Stroke;
+use super::text::{TextMetrics, TextStyle};
+use super::viewport::Viewport;
+
+/// Colors of the wires.
+const WIRE_COLORS: [(f32, f32, f32); 4] = [
+    (0.2, 0.2, 0.2),
+    (0.8, 0.2, 0.2),
+    (0.2, 0.5, 0.8),
+    (0.3, 0.6, 0.3),
+];
+
+/// Draw calls and time of the render pass of a scene.
+pub struct BenchmarkResult {
+    pub draw_calls: usize,
+    pub frame_time: Duration,
+}
+
+/// The results of a scene without and with batching.
+pub struct SceneBenchmark {
+    pub scene: &'static str,
+    /// A stroke per segment, how lines were drawn before batching.
+    pub per_segment: BenchmarkResult,
+    /// A stroke per `DrawLines` command, with batching turned off.
+    pub per_command: BenchmarkResult,
+    pub batched: BenchmarkResult,
+}
+
+/// Renders scenes of `segments` line segments into a bitmap, with and without batching.
+///
+/// The scenes are wiring diagrams: every wire is an L-shaped `DrawLines` command of two
+/// segments with its own color uniform. In the "grouped" scene consecutive wires share their
+/// color like the wires of a net do, the best case for batching; in the "alternating" scene
+/// every wire has another color than the one before, the worst case.
+pub fn line_benchmark(
+    segments: usize,
+    options: SnapshotOptions,
+) -> Result<Vec<SceneBenchmark>, piet::Error> {
+    let wires = (segments + 1) / 2;
+    let scenes: [(&'static str, Box<dyn Fn(usize) -> usize>); 2] = [
+        (
+            "grouped",
+            Box::new(move |wire| wire * WIRE_COLORS.len() / wires),
+        ),
+        ("alternating", Box::new(|wire| wire % WIRE_COLORS.len())),
+    ];
+
+    let mut results = Vec::with_capacity(scenes.len());
+
+    for (name, color) in &scenes {
+        let scene = wiring_scene(wires, options, color);
+
+        results.push(SceneBenchmark {
+            scene: *name,
+            per_segment: render_scene(&split_segments(&scene), options, false)?,
+            per_command: render_scene(&scene, options, false)?,
+            batched: render_scene(&scene, options, true)?,
+        });
+    }
+
+    Ok(results)
+}
+
+fn render_scene(
+    scene: &[RenderOp],
+    options: SnapshotOptions,
+    batching: bool,
+) -> Result<BenchmarkResult, piet::Error> {
+    let width = (options.size.width * options.scale).ceil() as usize;
+    let height = (options.size.height * options.scale).ceil() as usize;
+
+    let mut device = Device::new()?;
+    let mut target = device.bitmap_target(width, height, options.scale)?;
+    let mut ctx = target.render_context();
+    let mut layouts = LayoutCache::default();
+    let mut images = ImageCache::new();
+    let mut backend = PietBackend::new(&mut ctx, &mut layouts, &mut images);
+    let mut counter = CountingBackend::new(&mut backend);
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_batching(batching);
+    interpreter.set_viewport(Viewport::new(options.size, Point::ORIGIN, options.scale));
+
+    let frames = options.frames.max(1);
+    let start = Instant::now();
+
+    for _ in 0..frames {
+        counter.fill_quad(
+            Rect::from_origin_size(Point::ORIGIN, options.size),
+            &Color::WHITE,
+        );
+        interpreter.handle_render_commands(&mut counter, scene.iter().cloned().map(Ok));
+        interpreter.flush();
+    }
+
+    let frame_time = start.elapsed() / frames as u32;
+    let draw_calls = counter.draw_calls / frames;
+
+    ctx.finish()?;
+
+    Ok(BenchmarkResult {
+        draw_calls,
+        frame_time,
+    })
+}
+
+/// `color` returns the index into `WIRE_COLORS` of every wire.
+fn wiring_scene(
+    wires: usize,
+    options: SnapshotOptions,
+    color: impl Fn(usize) -> usize,
+) -> Vec<RenderOp> {
+    let columns = ((wires as f64).sqrt().ceil() as usize).max(1);
+    let cell_width = options.size.width as f32 / columns as f32;
+    let cell_height = options.size.height as f32 / columns as f32;
+    let mut ops = Vec::with_capacity(wires * 7);
+
+    for wire in 0..wires {
+        let (r, g, b) = WIRE_COLORS[color(wire)];
+        let x = (wire % columns) as f32 * cell_width;
+        let y = (wire / columns) as f32 * cell_height;
+
+        ops.push(RenderOp::PushColor(core::commands::Color::rgb(r, g, b)));
+        ops.push(RenderOp::SetColorUniform);
+        ops.push(RenderOp::PushVec2f(core::commands::Vec2f::new(x, y)));
+        ops.push(RenderOp::PushVec2f(core::commands::Vec2f::new(
+            x + cell_width,
+            y,
+        )));
+        ops.push(RenderOp::PushVec2f(core::commands::Vec2f::new(
+            x + cell_width,
+            y,
+        )));
+        ops.push(RenderOp::PushVec2f(core::commands::Vec2f::new(
+            x + cell_width,
+            y + cell_height,
+        )));
+        ops.push(RenderOp::DrawLines);
+    }
+
+    ops
+}
+
+/// Splits every `DrawLines` command into a command per segment.
+fn split_segments(scene: &[RenderOp]) -> Vec<RenderOp> {
+    let mut ops = Vec::with_capacity(scene.len() * 2);
+    let mut points = Vec::new();
+
+    for op in scene {
+        match op {
+            RenderOp::PushVec2f(point) => points.push(*point),
+            RenderOp::DrawLines => {
+                for segment in points.chunks_exact(2) {
+                    ops.push(RenderOp::PushVec2f(segment[0]));
+                    ops.push(RenderOp::PushVec2f(segment[1]));
+                    ops.push(RenderOp::DrawLines);
+                }
+
+                points.clear();
+            }
+            op => ops.push(op.clone()),
+        }
+    }
+
+    ops
+}
+
+/// Counts the draw calls sent to a backend.
+struct CountingBackend<'a, B: RenderBackend> {
+    backend: &'a mut B,
+    draw_calls: usize,
+}
+
+impl<'a, B: RenderBackend> CountingBackend<'a, B> {
+    fn new(backend: &'a mut B) -> Self {
+        CountingBackend {
+            backend,
+            draw_calls: 0,
+        }
+    }
+}
+
+impl<'a, B: RenderBackend> RenderBackend for CountingBackend<'a, B> {
+    fn fill_quad(&mut self, rect: Rect, color: &Color) {
+        self.draw_calls += 1;
+        self.backend.fill_quad(rect, color);
+    }
+
+    fn fill_circle(&mut self, circle: Circle, color: &Color) {
+        self.draw_calls += 1;
+        self.backend.fill_circle(circle, color);
+    }
+
+    fn stroke_shape(&mut self, shape: &impl Shape, color: &Color, stroke: &Stroke) {
+        self.draw_calls += 1;
+        self.backend.stroke_shape(shape, color, stroke);
+    }
+
+    fn fill_shape(&mut self, shape: &impl Shape, color: &Color) {
+        self.draw_calls += 1;
+        self.backend.fill_shape(shape, color);
+    }
+
+    fn fill_linear_gradient(
+        &mut self,
+        shape: &impl Shape,
+        start: (Point, &Color),
+        end: (Point, &Color),
+    ) {
+        self.draw_calls += 1;
+        self.backend.fill_linear_gradient(shape, start, end);
+    }
+
+    fn draw_image(&mut self, image: &Image, dest: Rect, source: Option<Rect>, opacity: f64) {
+        self.draw_calls += 1;
+        self.backend.draw_image(image, dest, source, opacity);
+    }
+
+    fn release_image(&mut self, image: &Image) {
+        self.backend.release_image(image);
+    }
+
+    fn draw_text(&mut self, text: &str, position: Point, style: &TextStyle, color: &Color) {
+        self.draw_calls += 1;
+        self.backend.draw_text(text, position, style, color);
+    }
+
+    fn measure_text(&mut self, text: &str, style: &TextStyle, color: &Color) -> TextMetrics {
+        self.backend.measure_text(text, style, color)
+    }
+
+    fn save(&mut self) {
+        self.backend.save();
+    }
+
+    fn restore(&mut self) {
+        self.backend.restore();
+    }
+
+    fn clip(&mut self, shape: &impl Shape) {
+        self.backend.clip(shape);
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.backend.transform(transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_commands_into_segments() {
+        let options = SnapshotOptions::default();
+        let scene = wiring_scene(3, options, |_| 0);
+        let draws = |ops: &[RenderOp]| {
+            ops.iter()
+                .filter(|op| matches!(op, RenderOp::DrawLines))
+                .count()
+        };
+
+        assert_eq!(draws(&scene), 3);
+        assert_eq!(draws(&split_segments(&scene)), 6);
+    }
+}
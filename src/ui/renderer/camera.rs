@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use druid::kurbo::{Affine, Vec2};
+use druid::Point;
+
+use super::viewport::Viewport;
+
+/// A view onto the scheme: translation, uniform zoom and rotation around the origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec2,
+    pub zoom: f64,
+    /// Rotation in radians.
+    pub rotation: f64,
+}
+
+impl Camera {
+    pub const IDENTITY: Camera = Camera {
+        position: Vec2::ZERO,
+        zoom: 1.,
+        rotation: 0.,
+    };
+
+    /// The transform from scheme space to canvas space.
+    pub fn transform(&self) -> Affine {
+        Affine::translate(self.position) * Affine::rotate(self.rotation) * Affine::scale(self.zoom)
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::IDENTITY
+    }
+}
+
+/// Cameras registered by the core, addressed by id.
+///
+/// Ids that were never updated resolve to the identity camera.
+#[derive(Clone, Default)]
+pub struct Cameras {
+    cameras: HashMap<i32, Camera>,
+}
+
+impl Cameras {
+    pub fn new() -> Self {
+        Cameras::default()
+    }
+
+    pub fn get(&self, id: i32) -> Camera {
+        self.cameras.get(&id).copied().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, id: i32) -> &mut Camera {
+        self.cameras.entry(id).or_default()
+    }
+}
+
+/// Rounds a point so that a line of `width` through it covers whole device pixels.
+///
+/// `transform` maps the point to the canvas of `viewport`. The pixel grid is the one of the
+/// window, so a canvas at a fractional origin still draws crisp lines. Lines an odd number of
+/// device pixels wide are centered on a pixel center, the others on a pixel edge; hairlines
+/// are treated as one device pixel wide.
+pub fn snap_to_pixel(transform: Affine, viewport: &Viewport, width: f64, point: Point) -> Point {
+    let scale = viewport.scale;
+    let to_device = Affine::scale(scale) * Affine::translate(viewport.origin.to_vec2()) * transform;
+    let zoom = transform.determinant().abs().sqrt();
+    let device_width = (width * zoom * scale).round().max(1.);
+    let offset = if device_width % 2. == 1. { 0.5 } else { 0. };
+
+    let device = to_device * point;
+    let snapped = Point::new(
+        (device.x - offset).round() + offset,
+        (device.y - offset).round() + offset,
+    );
+
+    to_device.inverse() * snapped
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::Size;
+
+    use super::*;
+
+    fn viewport(origin: Point, scale: f64) -> Viewport {
+        Viewport::new(Size::ZERO, origin, scale)
+    }
+
+    #[test]
+    fn snaps_hairlines_to_device_pixels() {
+        let point = Point::new(10.3, 20.8);
+
+        assert_eq!(
+            snap_to_pixel(Affine::IDENTITY, &viewport(Point::ORIGIN, 1.), 1., point),
+            Point::new(10.5, 20.5)
+        );
+        assert_eq!(
+            snap_to_pixel(Affine::IDENTITY, &viewport(Point::ORIGIN, 2.), 1., point),
+            Point::new(10.5, 21.)
+        );
+        assert_eq!(
+            snap_to_pixel(Affine::IDENTITY, &viewport(Point::ORIGIN, 2.), 0.5, point),
+            Point::new(10.25, 20.75)
+        );
+        assert_eq!(
+            snap_to_pixel(Affine::scale(2.), &viewport(Point::ORIGIN, 1.), 0.5, point),
+            Point::new(10.25, 20.75)
+        );
+    }
+
+    #[test]
+    fn snaps_to_the_pixels_of_the_window() {
+        let point = Point::new(10.5, 20.5);
+        let snapped = snap_to_pixel(
+            Affine::IDENTITY,
+            &viewport(Point::new(0.25, 0.75), 1.),
+            1.,
+            point,
+        );
+
+        assert!((snapped.x - 10.25).abs() < 1e-9);
+        assert!((snapped.y - 20.75).abs() < 1e-9);
+    }
+}
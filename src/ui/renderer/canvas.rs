@@ -0,0 +1,119 @@
+//! The frame of the canvas: background, rounded corners and border.
+
+use druid::kurbo::RoundedRect;
+use druid::{Color, Env, Point, Rect, Size};
+
+use super::backend::RenderBackend;
+use super::grid::GridColors;
+use super::stroke::Stroke;
+use crate::theme;
+
+/// The paint under everything the core draws.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    Solid(Color),
+    /// A gradient from the top to the bottom of the canvas.
+    VerticalGradient(Color, Color),
+}
+
+impl Background {
+    /// A solid background for equal colors, a vertical gradient otherwise.
+    pub fn from_colors(top: Color, bottom: Color) -> Self {
+        if top.as_rgba_u32() == bottom.as_rgba_u32() {
+            Background::Solid(top)
+        }
+        else {
+            Background::VerticalGradient(top, bottom)
+        }
+    }
+}
+
+/// How the canvas is framed, read from the theme.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanvasStyle {
+    pub background: Background,
+    pub corner_radius: f64,
+    pub border_color: Color,
+    /// No border is drawn when it's zero.
+    pub border_width: f64,
+    pub grid: GridColors,
+}
+
+impl CanvasStyle {
+    pub fn from_env(env: &Env) -> Self {
+        CanvasStyle {
+            background: Background::from_colors(
+                env.get(theme::CANVAS_BACKGROUND_COLOR),
+                env.get(theme::CANVAS_BACKGROUND_END_COLOR),
+            ),
+            corner_radius: env.get(theme::CANVAS_CORNER_RADIUS),
+            border_color: env.get(theme::CANVAS_BORDER_COLOR),
+            border_width: env.get(theme::CANVAS_BORDER_WIDTH),
+            grid: GridColors {
+                minor: env.get(theme::CANVAS_GRID_COLOR),
+                major: env.get(theme::CANVAS_GRID_MAJOR_COLOR),
+            },
+        }
+    }
+
+    /// Clips the backend to the rounded canvas.
+    pub fn clip<B: RenderBackend>(&self, backend: &mut B, size: Size) {
+        backend.clip(&self.rect(size));
+    }
+
+    /// Paints the background, `background` replaces the one of the theme.
+    pub fn paint_background<B: RenderBackend>(
+        &self,
+        backend: &mut B,
+        size: Size,
+        background: Option<&Background>,
+    ) {
+        let rect = self.rect(size);
+
+        match background.unwrap_or(&self.background) {
+            Background::Solid(color) => backend.fill_shape(&rect, color),
+            Background::VerticalGradient(top, bottom) => {
+                backend.fill_linear_gradient(
+                    &rect,
+                    (Point::ORIGIN, top),
+                    (Point::new(0., size.height), bottom),
+                )
+            }
+        }
+    }
+
+    fn rect(&self, size: Size) -> RoundedRect {
+        Rect::from_origin_size(Point::ORIGIN, size).to_rounded_rect(self.corner_radius)
+    }
+
+    /// Strokes the border inside the edge of the canvas, over the primitives of the core.
+    pub fn paint_border<B: RenderBackend>(&self, backend: &mut B, size: Size) {
+        if self.border_width <= 0. {
+            return;
+        }
+
+        let inset = self.border_width / 2.;
+        let rect = Rect::from_origin_size(Point::ORIGIN, size)
+            .inset(-inset)
+            .to_rounded_rect((self.corner_radius - inset).max(0.));
+
+        let stroke = Stroke {
+            width: self.border_width,
+            ..Stroke::default()
+        };
+
+        backend.stroke_shape(&rect, &self.border_color, &stroke);
+    }
+}
+
+impl Default for CanvasStyle {
+    fn default() -> Self {
+        CanvasStyle {
+            background: Background::Solid(Color::rgb8(0x2B, 0x2B, 0x2B)),
+            corner_radius: 4.,
+            border_color: Color::TRANSPARENT,
+            border_width: 0.,
+            grid: GridColors::default(),
+        }
+    }
+}
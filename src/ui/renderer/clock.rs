@@ -0,0 +1,80 @@
+//! Fixed timestep of the core simulation.
+
+use std::time::{Duration, Instant};
+
+/// The default simulation rate, 60 steps per second.
+pub const DEFAULT_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+
+/// Frames never run more steps than this to catch up, so a long stall doesn't make
+/// the following frames even slower.
+const MAX_STEPS_PER_FRAME: usize = 5;
+
+/// Turns the wall-clock time between painted frames into a number of fixed simulation steps.
+///
+/// The time that doesn't add up to a whole step is carried over to the next frame, so the
+/// simulation advances at the same rate however often the canvas is painted.
+pub struct SimulationClock {
+    timestep: Duration,
+    accumulator: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl SimulationClock {
+    pub fn new(timestep: Duration) -> Self {
+        SimulationClock {
+            timestep,
+            accumulator: Duration::from_secs(0),
+            last_frame: None,
+        }
+    }
+
+    /// Forgets the idle time, the next frame runs a single step.
+    pub fn resume(&mut self) {
+        self.last_frame = None;
+        self.accumulator = Duration::from_secs(0);
+    }
+
+    /// Returns the number of steps to run for a frame painted at `now`.
+    pub fn advance(&mut self, now: Instant) -> usize {
+        let last_frame = match self.last_frame.replace(now) {
+            Some(last_frame) => last_frame,
+            None => return 1,
+        };
+
+        self.accumulator += now.saturating_duration_since(last_frame);
+
+        let mut steps = 0;
+
+        while self.accumulator >= self.timestep && steps < MAX_STEPS_PER_FRAME {
+            self.accumulator -= self.timestep;
+            steps += 1;
+        }
+
+        // Drop the backlog that couldn't be caught up with.
+        if steps == MAX_STEPS_PER_FRAME {
+            self.accumulator = self.accumulator.min(self.timestep);
+        }
+
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_partial_steps_over() {
+        let start = Instant::now();
+        let mut clock = SimulationClock::new(Duration::from_millis(10));
+
+        assert_eq!(clock.advance(start), 1);
+        assert_eq!(clock.advance(start + Duration::from_millis(15)), 1);
+        assert_eq!(clock.advance(start + Duration::from_millis(20)), 1);
+        assert_eq!(clock.advance(start + Duration::from_millis(24)), 0);
+        assert_eq!(
+            clock.advance(start + Duration::from_millis(1000)),
+            MAX_STEPS_PER_FRAME
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Safe decoding of the command buffers produced by the core.
+//!
+//! The core exposes its execution and render commands as C arrays of tagged unions.
+//! Everything that touches those raw buffers lives here: the rest of the renderer
+//! only sees [`ExecOp`] and [`RenderOp`] values.
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+use tech_paws_core as core;
+
+use core::commands::{
+    Color, ExecutionCommand, ExecutionCommandType, RenderCommand, RenderCommandType, Vec2f,
+};
+
+/// A decoded execution command.
+#[derive(Clone)]
+pub enum ExecOp {
+    PushVec2f(Vec2f),
+    PushInt32(i32),
+    PushFloat32(f32),
+    UpdateCameraPosition,
+    UpdateCameraTransform,
+}
+
+/// A decoded render command.
+#[derive(Clone)]
+pub enum RenderOp {
+    PushColor(Color),
+    PushVec2f(Vec2f),
+    PushInt32(i32),
+    PushFloat32(f32),
+    PushString(String),
+    /// The `bytes` payload of the core, e.g. an encoded image.
+    PushBytes(Vec<u8>),
+    DrawText,
+    DrawLines,
+    DrawQuads,
+    DrawPoints,
+    StrokePath,
+    FillPath,
+    DrawArcs,
+    DrawEllipses,
+    DrawRoundedRects,
+    DrawImage,
+    RegisterImage,
+    ReleaseImage,
+    PushClipRect,
+    PopClip,
+    PushTransform,
+    PopTransform,
+    SetCamera,
+    SetColorUniform,
+    SetTextStyle,
+    SetStrokeStyle,
+    SetCanvasBackground,
+    SetGrid,
+}
+
+/// An error found while decoding a command buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The buffer pointer is null but the buffer claims to have items.
+    NullBuffer { length: usize },
+    /// The buffer pointer is not aligned for the command type.
+    MisalignedBuffer { address: usize },
+    /// The buffer length can't be addressed.
+    InvalidLength { length: usize },
+    /// The payload doesn't hold a valid value for the command type.
+    InvalidPayload { index: usize, command: &'static str },
+    /// The command type is not supported by the frontend.
+    UnsupportedCommand { index: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::NullBuffer { length } => {
+                write!(f, "command buffer is null but has {} items", length)
+            }
+            DecodeError::MisalignedBuffer { address } => {
+                write!(f, "command buffer at {:#x} is misaligned", address)
+            }
+            DecodeError::InvalidLength { length } => {
+                write!(f, "command buffer length {} is out of bounds", length)
+            }
+            DecodeError::InvalidPayload { index, command } => {
+                write!(f, "command #{} ({}) has an invalid payload", index, command)
+            }
+            DecodeError::UnsupportedCommand { index } => {
+                write!(f, "command #{} has an unsupported type", index)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decodes the execution commands of the current frame.
+///
+/// The buffer is owned by the core and is only valid until the next `tech_paws_core::flush`,
+/// so it's decoded at once into owned ops that can outlive it.
+pub fn exec_commands() -> Result<Vec<Result<ExecOp, DecodeError>>, DecodeError> {
+    let commands = core::c_get_exec_commands();
+    let length = commands.length as usize;
+    let items = checked_buffer(commands.items as *const ExecutionCommand, length)?;
+
+    // Safety: the buffer was checked and the core keeps its items alive until the next flush,
+    // which can't happen while they are decoded.
+    Ok((0..length)
+        .map(|index| unsafe { decode_exec_command(index, items.add(index)) })
+        .collect())
+}
+
+/// Decodes the render commands of the current render pass.
+///
+/// The buffer is owned by the core and is only valid until the next `tech_paws_core::flush`
+/// or `tech_paws_core::render_state_flush`, so it's decoded at once into owned ops.
+pub fn render_commands() -> Result<Vec<Result<RenderOp, DecodeError>>, DecodeError> {
+    let commands = core::c_get_render_commands();
+    let length = commands.length as usize;
+    let items = checked_buffer(commands.items as *const RenderCommand, length)?;
+
+    // Safety: see `exec_commands`.
+    Ok((0..length)
+        .map(|index| unsafe { decode_render_command(index, items.add(index)) })
+        .collect())
+}
+
+/// Checks that `length` items can be read from the buffer.
+fn checked_buffer<C>(items: *const C, length: usize) -> Result<*const C, DecodeError> {
+    if length == 0 {
+        return Ok(items);
+    }
+
+    if items.is_null() {
+        return Err(DecodeError::NullBuffer { length });
+    }
+
+    if items as usize % mem::align_of::<C>() != 0 {
+        return Err(DecodeError::MisalignedBuffer {
+            address: items as usize,
+        });
+    }
+
+    let max_length = isize::MAX as usize / mem::size_of::<C>().max(1);
+
+    if length > max_length {
+        return Err(DecodeError::InvalidLength { length });
+    }
+
+    Ok(items)
+}
+
+// The tags are C enums, read as the C `int` they are stored as.
+const _: [(); 0] = [(); (mem::size_of::<ExecutionCommandType>() != mem::size_of::<i32>()) as usize];
+const _: [(); 0] = [(); (mem::size_of::<RenderCommandType>() != mem::size_of::<i32>()) as usize];
+
+/// Reads the raw tag of a command.
+///
+/// The tag is never read as the Rust enum: a value the enum doesn't know would already be
+/// undefined behaviour, before it could be rejected. This relies on the core declaring
+/// `ExecutionCommandType` and `RenderCommandType` as `#[repr(i32)]`.
+///
+/// # Safety
+///
+/// `tag` must point to an initialized tag of a command.
+unsafe fn read_tag<T>(tag: *const T) -> i32 {
+    ptr::read(tag as *const i32)
+}
+
+/// Evaluates the arm of the command type whose discriminant is the raw tag.
+macro_rules! match_tag {
+    ($tag:expr, $ty:ident { $($variant:ident => $op:expr,)* _ => $default:expr $(,)? }) => {{
+        let tag = $tag;
+        $(if tag == $ty::$variant as i32 { $op } else)* { $default }
+    }};
+}
+
+/// # Safety
+///
+/// `command` must point to an initialized command, its tag may hold any value.
+unsafe fn decode_exec_command(
+    index: usize,
+    command: *const ExecutionCommand,
+) -> Result<ExecOp, DecodeError> {
+    let tag = read_tag(ptr::addr_of!((*command).command_type));
+    // Only the union field that corresponds to the tag is read.
+    let data = &*ptr::addr_of!((*command).data);
+
+    let op = match_tag!(tag, ExecutionCommandType {
+        PushVec2f => ExecOp::PushVec2f(data.vec2f),
+        PushInt32 => ExecOp::PushInt32(data.int32),
+        PushFloat32 => ExecOp::PushFloat32(data.float32),
+        UpdateCameraPosition => ExecOp::UpdateCameraPosition,
+        UpdateCameraTransform => ExecOp::UpdateCameraTransform,
+        _ => return Err(DecodeError::UnsupportedCommand { index }),
+    });
+
+    validate_exec_op(index, op)
+}
+
+/// # Safety
+///
+/// `command` must point to an initialized command, its tag may hold any value.
+unsafe fn decode_render_command(
+    index: usize,
+    command: *const RenderCommand,
+) -> Result<RenderOp, DecodeError> {
+    let tag = read_tag(ptr::addr_of!((*command).command_type));
+    // Only the union field that corresponds to the tag is read.
+    let data = &*ptr::addr_of!((*command).data);
+
+    let op = match_tag!(tag, RenderCommandType {
+        PushColor => RenderOp::PushColor(data.color),
+        PushVec2f => RenderOp::PushVec2f(data.vec2f),
+        PushInt32 => RenderOp::PushInt32(data.int32),
+        PushFloat32 => RenderOp::PushFloat32(data.float32),
+        PushString => RenderOp::PushString(data.string.data_to_string()),
+        PushBytes => RenderOp::PushBytes(data.bytes.data_to_vec()),
+        DrawText => RenderOp::DrawText,
+        DrawLines => RenderOp::DrawLines,
+        DrawQuads => RenderOp::DrawQuads,
+        DrawPoints => RenderOp::DrawPoints,
+        StrokePath => RenderOp::StrokePath,
+        FillPath => RenderOp::FillPath,
+        DrawArcs => RenderOp::DrawArcs,
+        DrawEllipses => RenderOp::DrawEllipses,
+        DrawRoundedRects => RenderOp::DrawRoundedRects,
+        DrawImage => RenderOp::DrawImage,
+        RegisterImage => RenderOp::RegisterImage,
+        ReleaseImage => RenderOp::ReleaseImage,
+        PushClipRect => RenderOp::PushClipRect,
+        PopClip => RenderOp::PopClip,
+        PushTransform => RenderOp::PushTransform,
+        PopTransform => RenderOp::PopTransform,
+        SetCamera => RenderOp::SetCamera,
+        SetColorUniform => RenderOp::SetColorUniform,
+        SetTextStyle => RenderOp::SetTextStyle,
+        SetStrokeStyle => RenderOp::SetStrokeStyle,
+        SetCanvasBackground => RenderOp::SetCanvasBackground,
+        SetGrid => RenderOp::SetGrid,
+        _ => return Err(DecodeError::UnsupportedCommand { index }),
+    });
+
+    validate_render_op(index, op)
+}
+
+/// Rejects payloads the interpreter can't use.
+pub fn validate_exec_op(index: usize, op: ExecOp) -> Result<ExecOp, DecodeError> {
+    match op {
+        ExecOp::PushVec2f(vec) if !is_finite_vec2f(vec) => {
+            Err(DecodeError::InvalidPayload {
+                index,
+                command: "PushVec2f",
+            })
+        }
+        ExecOp::PushFloat32(value) if !value.is_finite() => {
+            Err(DecodeError::InvalidPayload {
+                index,
+                command: "PushFloat32",
+            })
+        }
+        op => Ok(op),
+    }
+}
+
+/// Rejects payloads the interpreter can't use.
+pub fn validate_render_op(index: usize, op: RenderOp) -> Result<RenderOp, DecodeError> {
+    match op {
+        RenderOp::PushVec2f(vec) if !is_finite_vec2f(vec) => {
+            Err(DecodeError::InvalidPayload {
+                index,
+                command: "PushVec2f",
+            })
+        }
+        RenderOp::PushFloat32(value) if !value.is_finite() => {
+            Err(DecodeError::InvalidPayload {
+                index,
+                command: "PushFloat32",
+            })
+        }
+        RenderOp::PushColor(color) if !is_valid_color(color) => {
+            Err(DecodeError::InvalidPayload {
+                index,
+                command: "PushColor",
+            })
+        }
+        op => Ok(op),
+    }
+}
+
+fn is_finite_vec2f(vec: Vec2f) -> bool {
+    vec.x.is_finite() && vec.y.is_finite()
+}
+
+fn is_valid_color(color: Color) -> bool {
+    [color.r, color.g, color.b, color.a]
+        .iter()
+        .all(|component| component.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::NonNull;
+
+    #[test]
+    fn rejects_invalid_buffers() {
+        let null: *const RenderCommand = ptr::null();
+        let aligned = NonNull::<RenderCommand>::dangling().as_ptr() as *const RenderCommand;
+        let misaligned = (aligned as usize + 1) as *const RenderCommand;
+
+        assert!(checked_buffer(null, 0).is_ok());
+        assert_eq!(
+            checked_buffer(null, 3),
+            Err(DecodeError::NullBuffer { length: 3 })
+        );
+        assert_eq!(
+            checked_buffer(misaligned, 1),
+            Err(DecodeError::MisalignedBuffer {
+                address: misaligned as usize
+            })
+        );
+        assert_eq!(
+            checked_buffer(aligned, usize::MAX),
+            Err(DecodeError::InvalidLength { length: usize::MAX })
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_payloads() {
+        let invalid = |command| Err(DecodeError::InvalidPayload { index: 2, command });
+
+        assert_eq!(
+            validate_render_op(2, RenderOp::PushFloat32(f32::NAN)).map(|_| ()),
+            invalid("PushFloat32")
+        );
+        assert_eq!(
+            validate_render_op(2, RenderOp::PushVec2f(Vec2f::new(0., f32::INFINITY))).map(|_| ()),
+            invalid("PushVec2f")
+        );
+        assert_eq!(
+            validate_render_op(2, RenderOp::PushColor(Color::rgb(f32::NAN, 0., 0.))).map(|_| ()),
+            invalid("PushColor")
+        );
+        assert_eq!(
+            validate_exec_op(2, ExecOp::PushFloat32(f32::NEG_INFINITY)).map(|_| ()),
+            invalid("PushFloat32")
+        );
+        assert!(validate_render_op(2, RenderOp::PushFloat32(1.5)).is_ok());
+    }
+}
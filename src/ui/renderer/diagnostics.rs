@@ -0,0 +1,135 @@
+//! Problems found while interpreting the command streams of the core.
+
+use std::fmt;
+
+use super::commands::DecodeError;
+
+/// The command stream a diagnostic was found in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pass {
+    Exec,
+    Measure,
+    Render,
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pass::Exec => write!(f, "exec"),
+            Pass::Measure => write!(f, "measure"),
+            Pass::Render => write!(f, "render"),
+        }
+    }
+}
+
+/// Number of arguments on each of the argument stacks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Arguments {
+    pub int32: usize,
+    pub float32: usize,
+    pub vec2f: usize,
+    pub color: usize,
+    pub string: usize,
+    pub bytes: usize,
+}
+
+impl Arguments {
+    pub fn saturating_sub(self, other: Arguments) -> Arguments {
+        Arguments {
+            int32: self.int32.saturating_sub(other.int32),
+            float32: self.float32.saturating_sub(other.float32),
+            vec2f: self.vec2f.saturating_sub(other.vec2f),
+            color: self.color.saturating_sub(other.color),
+            string: self.string.saturating_sub(other.string),
+            bytes: self.bytes.saturating_sub(other.bytes),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Arguments::default()
+    }
+}
+
+impl fmt::Display for Arguments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [
+            (self.int32, "int32"),
+            (self.float32, "float32"),
+            (self.vec2f, "vec2f"),
+            (self.color, "color"),
+            (self.string, "string"),
+            (self.bytes, "bytes"),
+        ];
+
+        let mut separator = "";
+
+        for (count, name) in counts.iter().filter(|(count, _)| *count > 0) {
+            write!(f, "{}{} {}", separator, count, name)?;
+            separator = ", ";
+        }
+
+        Ok(())
+    }
+}
+
+/// A malformed command; the command is skipped or, for leftovers, executed with the
+/// arguments it understands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    Decode(DecodeError),
+    MissingArgument {
+        command: &'static str,
+        argument: &'static str,
+    },
+    InvalidArgument {
+        command: &'static str,
+        argument: &'static str,
+    },
+    Leftovers {
+        command: &'static str,
+        arguments: Arguments,
+    },
+    UnbalancedPop {
+        command: &'static str,
+    },
+    /// The render pass ended with pushed clips or transforms, they are popped by the renderer.
+    Unclosed {
+        clips: usize,
+        transforms: usize,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::Decode(err) => write!(f, "{}", err),
+            Diagnostic::MissingArgument { command, argument } => {
+                write!(
+                    f,
+                    "{} is missing the {} argument, skipped",
+                    command, argument
+                )
+            }
+            Diagnostic::InvalidArgument { command, argument } => {
+                write!(
+                    f,
+                    "{} has an invalid {} argument, skipped",
+                    command, argument
+                )
+            }
+            Diagnostic::Leftovers { command, arguments } => {
+                write!(f, "{} left unused arguments: {}", command, arguments)
+            }
+            Diagnostic::UnbalancedPop { command } => {
+                write!(f, "{} without a matching push, skipped", command)
+            }
+            Diagnostic::Unclosed { clips, transforms } => {
+                write!(
+                    f,
+                    "the pass ended with {} clips and {} transforms still pushed",
+                    clips, transforms
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! The infinite background grid of the scheme.
+
+use druid::kurbo::{Affine, BezPath, Circle, Shape};
+use druid::{Color, Point, Rect, Size};
+
+use super::backend::RenderBackend;
+use super::camera;
+use super::stroke::Stroke;
+use super::viewport::Viewport;
+
+/// Grid lines closer than this on the canvas are merged into coarser levels.
+const MIN_SPACING: f64 = 8.;
+const DEFAULT_MAJOR_EVERY: i32 = 5;
+const DOT_RADIUS: f64 = 1.;
+const MAJOR_DOT_RADIUS: f64 = 1.5;
+/// Maximum distance of the flattened dots from exact circles.
+const DOT_TOLERANCE: f64 = 0.1;
+
+/// Whether the grid is drawn as lines or as dots at the intersections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridStyle {
+    Lines,
+    Dots,
+}
+
+impl GridStyle {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => GridStyle::Dots,
+            _ => GridStyle::Lines,
+        }
+    }
+}
+
+/// Colors of the grid, read from the theme.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridColors {
+    pub minor: Color,
+    pub major: Color,
+}
+
+impl Default for GridColors {
+    fn default() -> Self {
+        GridColors {
+            minor: Color::rgba8(0xFF, 0xFF, 0xFF, 0x10),
+            major: Color::rgba8(0xFF, 0xFF, 0xFF, 0x28),
+        }
+    }
+}
+
+/// A grid in scheme coordinates, set by the core.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    /// The distance between the minor lines in scheme units.
+    pub spacing: f64,
+    /// Every this many minor lines there is a major one.
+    pub major_every: i32,
+    pub style: GridStyle,
+    /// The camera the grid is seen through.
+    pub camera_id: i32,
+}
+
+impl Grid {
+    pub fn new(spacing: f64, major_every: Option<i32>, style: GridStyle, camera_id: i32) -> Self {
+        Grid {
+            spacing,
+            major_every: major_every.unwrap_or(DEFAULT_MAJOR_EVERY).max(1),
+            style,
+            camera_id,
+        }
+    }
+
+    /// Returns the minor spacing drawn at the zoom and how visible the minor lines are,
+    /// from 0 to 1.
+    ///
+    /// When the lines get too dense the major lines become the minor ones, the minor lines
+    /// fade in as they move apart so zooming doesn't make them pop.
+    pub fn level(&self, zoom: f64) -> (f64, f64) {
+        let factor = if self.major_every > 1 {
+            self.major_every as f64
+        }
+        else {
+            2.
+        };
+        let mut spacing = self.spacing;
+
+        while spacing * zoom < MIN_SPACING {
+            spacing *= factor;
+        }
+
+        let fade = (spacing * zoom / MIN_SPACING - 1.).min(1.);
+        (spacing, fade)
+    }
+
+    /// The spacing of the minor lines drawn through `transform` in scheme units, the distance
+    /// the core snaps to; `None` if the grid isn't drawn.
+    pub fn snap_spacing(&self, transform: Affine) -> Option<f64> {
+        let zoom = zoom(transform)?;
+        Some(self.level(zoom).0)
+    }
+
+    /// Draws the grid over the canvas of `viewport` seen through `transform`.
+    pub fn draw<B: RenderBackend>(
+        &self,
+        backend: &mut B,
+        transform: Affine,
+        viewport: &Viewport,
+        colors: &GridColors,
+    ) {
+        let zoom = match zoom(transform) {
+            Some(zoom) if self.spacing > 0. => zoom,
+            _ => return,
+        };
+
+        let (spacing, fade) = self.level(zoom);
+        let visible = visible_rect(transform, viewport.size);
+        let columns = lines(visible.x0, visible.x1, spacing);
+        let rows = lines(visible.y0, visible.y1, spacing);
+        let is_major = |index: i64| index % self.major_every as i64 == 0;
+
+        let mut minor = BezPath::new();
+        let mut major = BezPath::new();
+
+        match self.style {
+            GridStyle::Lines => {
+                // Hairlines, one device pixel wide.
+                let width = 1. / viewport.scale;
+                let snap = |point: Point| {
+                    camera::snap_to_pixel(Affine::IDENTITY, viewport, width, transform * point)
+                };
+
+                for index in columns {
+                    let x = index as f64 * spacing;
+                    let path = if is_major(index) {
+                        &mut major
+                    }
+                    else {
+                        &mut minor
+                    };
+                    path.move_to(snap(Point::new(x, visible.y0)));
+                    path.line_to(snap(Point::new(x, visible.y1)));
+                }
+
+                for index in rows {
+                    let y = index as f64 * spacing;
+                    let path = if is_major(index) {
+                        &mut major
+                    }
+                    else {
+                        &mut minor
+                    };
+                    path.move_to(snap(Point::new(visible.x0, y)));
+                    path.line_to(snap(Point::new(visible.x1, y)));
+                }
+
+                let stroke = Stroke {
+                    width,
+                    ..Stroke::default()
+                };
+
+                backend.stroke_shape(&minor, &faded(&colors.minor, fade), &stroke);
+                backend.stroke_shape(&major, &colors.major, &stroke);
+            }
+            GridStyle::Dots => {
+                for column in columns {
+                    for row in rows.clone() {
+                        let point = Point::new(column as f64 * spacing, row as f64 * spacing);
+                        let center = transform * point;
+
+                        if is_major(column) && is_major(row) {
+                            add_dot(&mut major, Circle::new(center, MAJOR_DOT_RADIUS));
+                        }
+                        else {
+                            add_dot(&mut minor, Circle::new(center, DOT_RADIUS));
+                        }
+                    }
+                }
+
+                backend.fill_shape(&minor, &faded(&colors.minor, fade));
+                backend.fill_shape(&major, &colors.major);
+            }
+        }
+    }
+}
+
+fn zoom(transform: Affine) -> Option<f64> {
+    let zoom = transform.determinant().abs().sqrt();
+    Some(zoom).filter(|zoom| *zoom > 0. && zoom.is_finite())
+}
+
+/// The bounding box of the canvas in scheme coordinates.
+fn visible_rect(transform: Affine, size: Size) -> Rect {
+    let inverse = transform.inverse();
+    let corners = [
+        Point::ORIGIN,
+        Point::new(size.width, 0.),
+        Point::new(0., size.height),
+        Point::new(size.width, size.height),
+    ];
+
+    corners
+        .iter()
+        .map(|corner| inverse * *corner)
+        .fold(None, |rect: Option<Rect>, point| {
+            Some(match rect {
+                Some(rect) => rect.union_pt(point),
+                None => Rect::from_points(point, point),
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// The indices of the lines between `start` and `end`.
+///
+/// The float to int casts saturate, so far away canvases get an empty or clamped range
+/// instead of overflowing.
+fn lines(start: f64, end: f64, spacing: f64) -> std::ops::Range<i64> {
+    (start / spacing).ceil() as i64..((end / spacing).floor() as i64).saturating_add(1)
+}
+
+fn add_dot(path: &mut BezPath, dot: Circle) {
+    for element in dot.path_elements(DOT_TOLERANCE) {
+        path.push(element);
+    }
+}
+
+fn faded(color: &Color, fade: f64) -> Color {
+    let rgba = color.as_rgba_u32();
+    let alpha = (rgba & 0xFF) as f64 * fade;
+
+    Color::from_rgba32_u32((rgba & !0xFF) | alpha.round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_dense_lines_into_major_ones() {
+        let grid = Grid::new(10., Some(5), GridStyle::Lines, 0);
+
+        assert_eq!(grid.level(1.), (10., 0.25));
+        assert_eq!(grid.level(2.), (10., 1.));
+        assert_eq!(grid.level(0.5), (50., 1.));
+        assert_eq!(grid.level(0.04), (250., 0.25));
+    }
+
+    #[test]
+    fn clamps_far_away_lines() {
+        assert_eq!(lines(-25., 25., 10.), -2..3);
+        assert_eq!(lines(1e30, 1e30 + 10., 1.), i64::MAX..i64::MAX);
+        assert_eq!(
+            lines(-1e30, -1e30 + 10., 1.),
+            i64::MIN..i64::MIN.saturating_add(1)
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Rendering of the canvas into a bitmap without a window.
+
+use std::path::Path;
+use std::time::Duration;
+
+use druid::piet::{self, Device, RenderContext};
+use druid::{Data, Point, Size};
+
+use super::backend::{PietBackend, RenderBackend};
+use super::images::ImageCache;
+use super::layout_cache::LayoutCache;
+use super::viewport::Viewport;
+use super::Renderer;
+
+/// How long a snapshot waits for the images of the core to be decoded.
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parameters of a headless snapshot.
+#[derive(Clone, Copy)]
+pub struct SnapshotOptions {
+    /// The viewport size in device independent pixels.
+    pub size: Size,
+    /// The number of bitmap pixels per device independent pixel.
+    pub scale: f64,
+    /// The number of core frames to run before the snapshot is taken.
+    pub frames: usize,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            size: Size::new(1024., 700.),
+            scale: 1.,
+            frames: 1,
+        }
+    }
+}
+
+impl<T: Data> Renderer<T> {
+    /// Runs the core into an in-memory bitmap and saves it as a PNG file.
+    pub fn save_snapshot(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: SnapshotOptions,
+    ) -> Result<(), piet::Error> {
+        let width = (options.size.width * options.scale).ceil() as usize;
+        let height = (options.size.height * options.scale).ceil() as usize;
+
+        let mut device = Device::new()?;
+        let mut target = device.bitmap_target(width, height, options.scale)?;
+
+        {
+            let mut ctx = target.render_context();
+            let mut layouts = LayoutCache::default();
+            let mut images = ImageCache::new();
+            let mut backend = PietBackend::new(&mut ctx, &mut layouts, &mut images);
+
+            self.set_viewport(Viewport::new(options.size, Point::ORIGIN, options.scale));
+
+            for _ in 0..options.frames.max(1) {
+                backend.save();
+                self.paint_frame(&mut backend, options.size, 1);
+                backend.restore();
+            }
+
+            // Images are decoded in the background, another frame draws the ones registered
+            // by the last frame.
+            if self.interpreter.is_loading_images() {
+                self.interpreter.wait_for_images(IMAGE_TIMEOUT);
+                backend.save();
+                self.paint_frame(&mut backend, options.size, 1);
+                backend.restore();
+            }
+
+            ctx.finish()?;
+        }
+
+        target.save_to_file(path)
+    }
+}
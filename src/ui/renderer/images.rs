@@ -0,0 +1,350 @@
+//! Images registered by the core.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Backend copies kept per image, enough for an image drawn opaque and fading at once.
+const COPIES_PER_IMAGE: usize = 2;
+/// Threads decoding the registered images, a burst of registrations is queued for them.
+const DECODE_WORKERS: usize = 2;
+
+/// Where the encoded data of an image comes from.
+pub enum ImageSource {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+/// A decoded image.
+pub struct Image {
+    /// Unique for every registration, so backends can cache their own copies even when
+    /// the core reuses an id for another image.
+    pub generation: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Unpremultiplied RGBA pixels, row by row.
+    pub pixels: Vec<u8>,
+    /// The data the image was decoded from, for backends that embed it as is.
+    pub encoded: Vec<u8>,
+}
+
+impl Image {
+    /// Returns the pixels with the alpha channel multiplied by `opacity`.
+    pub fn pixels_with_opacity(&self, opacity: u8) -> Vec<u8> {
+        let mut pixels = self.pixels.clone();
+
+        if opacity < u8::MAX {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel[3] = (pixel[3] as u16 * opacity as u16 / u8::MAX as u16) as u8;
+            }
+        }
+
+        pixels
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(PathBuf, io::Error),
+    Decode(image::ImageError),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            ImageError::Decode(err) => write!(f, "can't decode the image: {}", err),
+        }
+    }
+}
+
+impl Error for ImageError {}
+
+/// A queued background decode: the id, the generation and where the image comes from.
+type Job = (i32, u64, ImageSource);
+
+/// A finished background decode: the id, the generation and the image.
+type Decoded = (i32, u64, Result<Image, ImageError>);
+
+/// The images of the core, addressed by id.
+///
+/// Images are read and decoded by a few background threads so a large image doesn't stall
+/// the frame registering it; until then drawing the id draws nothing.
+pub struct ImageAtlas {
+    images: HashMap<i32, Image>,
+    /// The generation being decoded for every id still loading.
+    pending: HashMap<i32, u64>,
+    next_generation: u64,
+    /// The queue of the decode threads, started with the first registration.
+    jobs: Option<Sender<Job>>,
+    sender: Sender<Decoded>,
+    receiver: Receiver<Decoded>,
+}
+
+impl ImageAtlas {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        ImageAtlas {
+            images: HashMap::new(),
+            pending: HashMap::new(),
+            next_generation: 0,
+            jobs: None,
+            sender,
+            receiver,
+        }
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Image> {
+        self.images.get(&id)
+    }
+
+    /// Whether the id is registered, even if its image is still loading.
+    pub fn contains(&self, id: i32) -> bool {
+        self.images.contains_key(&id) || self.pending.contains_key(&id)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Starts decoding the image for the id, returning the image it replaces.
+    pub fn register(&mut self, id: i32, source: ImageSource) -> Option<Image> {
+        let generation = self.next_generation;
+
+        self.next_generation += 1;
+        self.pending.insert(id, generation);
+
+        if self.jobs().send((id, generation, source)).is_err() {
+            // All the decode threads panicked.
+            self.pending.remove(&id);
+            log::error!("Failed to register image {}: no decode thread left", id);
+        }
+
+        self.images.remove(&id)
+    }
+
+    pub fn release(&mut self, id: i32) -> Option<Image> {
+        self.pending.remove(&id);
+        self.images.remove(&id)
+    }
+
+    /// Stores the images decoded since the last call.
+    pub fn receive(&mut self) {
+        while let Ok(decoded) = self.receiver.try_recv() {
+            self.store(decoded);
+        }
+    }
+
+    /// Blocks until the pending images are decoded or `timeout` passed.
+    pub fn wait(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+
+        while self.is_loading() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match self.receiver.recv_timeout(remaining) {
+                Ok(decoded) => self.store(decoded),
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn jobs(&mut self) -> &Sender<Job> {
+        let sender = &self.sender;
+
+        self.jobs.get_or_insert_with(|| spawn_workers(sender))
+    }
+
+    fn store(&mut self, (id, generation, result): Decoded) {
+        // The id was released or registered again while the image was decoded.
+        if self.pending.get(&id) != Some(&generation) {
+            return;
+        }
+
+        self.pending.remove(&id);
+
+        match result {
+            Ok(image) => {
+                self.images.insert(id, image);
+            }
+            Err(err) => log::error!("Failed to register image {}: {}", id, err),
+        }
+    }
+}
+
+impl Default for ImageAtlas {
+    fn default() -> Self {
+        ImageAtlas::new()
+    }
+}
+
+/// Starts the decode threads, they stop once the returned queue is dropped.
+fn spawn_workers(sender: &Sender<Decoded>) -> Sender<Job> {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let queue = Arc::new(Mutex::new(queue));
+
+    for _ in 0..DECODE_WORKERS {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+
+        thread::spawn(move || decode_jobs(&queue, &sender));
+    }
+
+    jobs
+}
+
+fn decode_jobs(queue: &Mutex<Receiver<Job>>, sender: &Sender<Decoded>) {
+    loop {
+        // The lock is released before decoding, so the other threads can take the next job.
+        let job = match queue.lock() {
+            Ok(queue) => queue.recv(),
+            Err(_) => return,
+        };
+
+        let (id, generation, source) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        // The atlas may be gone by the time the image is decoded.
+        if sender
+            .send((id, generation, decode(generation, source)))
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+fn decode(generation: u64, source: ImageSource) -> Result<Image, ImageError> {
+    let encoded = match source {
+        ImageSource::Bytes(bytes) => bytes,
+        ImageSource::File(path) => fs::read(&path).map_err(|err| ImageError::Io(path, err))?,
+    };
+
+    let decoded = image::load_from_memory(&encoded)
+        .map_err(ImageError::Decode)?
+        .into_rgba();
+
+    Ok(Image {
+        generation,
+        width: decoded.width() as usize,
+        height: decoded.height() as usize,
+        pixels: decoded.into_raw(),
+        encoded,
+    })
+}
+
+/// Backend specific copies of the images, kept across frames.
+///
+/// Piet can't draw an image with an opacity, so there is a copy for every opacity an image
+/// is drawn with. Only the most recently used copies of an image are kept, an image fading
+/// in or out is uploaded again on every frame but doesn't pile up copies.
+pub struct ImageCache<I> {
+    /// The copies of every image generation by opacity, most recently used first.
+    images: HashMap<u64, Vec<(u8, I)>>,
+}
+
+impl<I> ImageCache<I> {
+    pub fn new() -> Self {
+        ImageCache {
+            images: HashMap::new(),
+        }
+    }
+
+    /// Returns the copy of the image with the opacity, making it with `make` on a miss.
+    pub fn get_or_make(
+        &mut self,
+        image: &Image,
+        opacity: u8,
+        make: impl FnOnce(Vec<u8>) -> Option<I>,
+    ) -> Option<&I> {
+        let copies = self.images.entry(image.generation).or_default();
+
+        match copies.iter().position(|(copy, _)| *copy == opacity) {
+            Some(index) => copies[..=index].rotate_right(1),
+            None => {
+                let copy = make(image.pixels_with_opacity(opacity))?;
+                copies.truncate(COPIES_PER_IMAGE - 1);
+                copies.insert(0, (opacity, copy));
+            }
+        }
+
+        copies.first().map(|(_, copy)| copy)
+    }
+
+    pub fn release(&mut self, image: &Image) {
+        self.images.remove(&image.generation);
+    }
+}
+
+impl<I> Default for ImageCache<I> {
+    fn default() -> Self {
+        ImageCache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        Image {
+            generation: 0,
+            width: 1,
+            height: 1,
+            pixels: vec![0xFF; 4],
+            encoded: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_recent_copies_of_an_image() {
+        let mut cache = ImageCache::new();
+        let image = image();
+
+        for opacity in 0..=u8::MAX {
+            let copy = cache.get_or_make(&image, opacity, |pixels| Some(pixels[3]));
+            assert_eq!(copy, Some(&opacity));
+        }
+
+        assert_eq!(cache.images[&image.generation].len(), COPIES_PER_IMAGE);
+
+        // The most recent copy is reused instead of made again.
+        let copy = cache.get_or_make(&image, u8::MAX, |_| panic!("the copy is cached"));
+        assert_eq!(copy, Some(&u8::MAX));
+
+        cache.release(&image);
+        assert!(cache.images.is_empty());
+    }
+
+    #[test]
+    fn decodes_more_images_than_threads() {
+        let mut png = Vec::new();
+        image::png::PngEncoder::new(&mut png)
+            .encode(&[0xFF; 8], 2, 1, image::ColorType::Rgba8)
+            .unwrap();
+
+        let mut atlas = ImageAtlas::new();
+        let count = DECODE_WORKERS as i32 * 3;
+
+        for id in 0..count {
+            atlas.register(id, ImageSource::Bytes(png.clone()));
+        }
+
+        atlas.register(count, ImageSource::Bytes(vec![0; 4]));
+        atlas.wait(Duration::from_secs(10));
+
+        assert!(!atlas.is_loading());
+        assert!((0..count).all(|id| atlas.get(id).map(|image| image.width) == Some(2)));
+        assert!(atlas.get(count).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+//! Translation of druid input events into core request commands.
+
+use druid::kurbo::Vec2;
+use druid::{KbKey, KeyEvent, Modifiers, MouseButton, MouseButtons, MouseEvent, Point};
+
+use tech_paws_core as core;
+
+/// Wheel distance that zooms by a factor of `e`.
+const WHEEL_ZOOM_DISTANCE: f64 = 500.;
+
+pub const MODIFIER_SHIFT: i32 = 1;
+pub const MODIFIER_CTRL: i32 = 1 << 1;
+pub const MODIFIER_ALT: i32 = 1 << 2;
+pub const MODIFIER_META: i32 = 1 << 3;
+
+/// Druid only reports a single mouse, it is always the pointer with this id.
+const MOUSE_POINTER_ID: i32 = 0;
+
+const BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Right,
+    MouseButton::Middle,
+    MouseButton::X1,
+    MouseButton::X2,
+];
+
+/// Returns the core code of the button: 0 for none, then left, right, middle, x1 and x2.
+pub fn button_code(button: MouseButton) -> i32 {
+    BUTTONS
+        .iter()
+        .position(|candidate| *candidate == button)
+        .map(|index| index as i32 + 1)
+        .unwrap_or(0)
+}
+
+/// Packs the held buttons into a bit set, bit `n - 1` is set for the button with code `n`.
+pub fn buttons_mask(buttons: &MouseButtons) -> i32 {
+    BUTTONS
+        .iter()
+        .filter(|button| buttons.contains(**button))
+        .fold(0, |mask, button| mask | 1 << (button_code(*button) - 1))
+}
+
+/// Packs the modifier keys into the bit set the core expects.
+pub fn modifiers_mask(mods: &Modifiers) -> i32 {
+    let mut mask = 0;
+
+    if mods.shift() {
+        mask |= MODIFIER_SHIFT;
+    }
+    if mods.ctrl() {
+        mask |= MODIFIER_CTRL;
+    }
+    if mods.alt() {
+        mask |= MODIFIER_ALT;
+    }
+    if mods.meta() {
+        mask |= MODIFIER_META;
+    }
+
+    mask
+}
+
+pub fn vec2f(point: Point) -> core::commands::Vec2f {
+    core::commands::Vec2f::new(point.x as f32, point.y as f32)
+}
+
+/// Converts a mouse event into the pointer payload of the touch requests.
+///
+/// The touch requests of the core take it in place of the bare position they used to take.
+pub fn touch(event: &MouseEvent) -> core::commands::Touch {
+    core::commands::Touch {
+        position: vec2f(event.pos),
+        button: button_code(event.button),
+        buttons: buttons_mask(&event.buttons),
+        modifiers: modifiers_mask(&event.mods),
+        click_count: event.count as i32,
+        pointer_id: MOUSE_POINTER_ID,
+    }
+}
+
+/// Forwards a key press, and the text it produces, if any.
+///
+/// Keys are sent by their W3C names, e.g. `"a"`, `"Enter"` or `"ArrowLeft"` for the key
+/// and `"KeyA"` for the physical code. The core takes the text separately, through
+/// `push_on_text_input_request_command`, as it already accounts for the layout and Shift.
+pub fn push_key_down(event: &KeyEvent) {
+    core::push_on_key_down_request_command(key(event));
+
+    if let KbKey::Character(text) = &event.key {
+        if !event.mods.ctrl() && !event.mods.meta() {
+            core::push_on_text_input_request_command(text);
+        }
+    }
+}
+
+pub fn push_key_up(event: &KeyEvent) {
+    core::push_on_key_up_request_command(key(event));
+}
+
+fn key(event: &KeyEvent) -> core::commands::Key {
+    core::commands::Key {
+        key: event.key.to_string(),
+        code: event.code.to_string(),
+        modifiers: modifiers_mask(&event.mods),
+        repeat: event.repeat,
+    }
+}
+
+/// What a wheel movement asks the core to do with the scheme.
+#[derive(Debug, PartialEq)]
+enum WheelGesture {
+    /// Zoom around the cursor by the factor.
+    Zoom(f64),
+    /// Move the content by the delta.
+    Pan(Vec2),
+}
+
+/// Ctrl+wheel zooms around the cursor, any other wheel movement pans the scheme;
+/// Shift turns vertical wheel movement into horizontal panning.
+fn wheel_gesture(delta: Vec2, mods: &Modifiers) -> WheelGesture {
+    if mods.ctrl() {
+        return WheelGesture::Zoom((-delta.y / WHEEL_ZOOM_DISTANCE).exp());
+    }
+
+    let delta = if mods.shift() && delta.x == 0. {
+        Vec2::new(delta.y, 0.)
+    }
+    else {
+        delta
+    };
+
+    // The wheel delta is the amount the viewport scrolls by, the content moves opposite.
+    WheelGesture::Pan(-delta)
+}
+
+/// Forwards a mouse wheel or trackpad scroll, see [`wheel_gesture`].
+///
+/// Both core requests take the cursor position, then the zoom factor or the pan delta, then
+/// the [`modifiers_mask`].
+pub fn push_wheel(event: &MouseEvent) {
+    let mods = modifiers_mask(&event.mods);
+    let position = vec2f(event.pos);
+
+    match wheel_gesture(event.wheel_delta, &event.mods) {
+        WheelGesture::Zoom(factor) => {
+            core::push_on_zoom_request_command(position, factor as f32, mods)
+        }
+        WheelGesture::Pan(delta) => {
+            core::push_on_pan_request_command(position, vec2f(delta.to_point()), mods)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_buttons_in_core_order() {
+        assert_eq!(button_code(MouseButton::None), 0);
+        assert_eq!(button_code(MouseButton::Left), 1);
+        assert_eq!(button_code(MouseButton::Right), 2);
+        assert_eq!(button_code(MouseButton::Middle), 3);
+        assert_eq!(button_code(MouseButton::X1), 4);
+        assert_eq!(button_code(MouseButton::X2), 5);
+    }
+
+    #[test]
+    fn packs_held_buttons() {
+        assert_eq!(buttons_mask(&MouseButtons::new()), 0);
+        assert_eq!(
+            buttons_mask(&MouseButtons::new().with(MouseButton::Left)),
+            0b1
+        );
+
+        let buttons = MouseButtons::new()
+            .with(MouseButton::Right)
+            .with(MouseButton::Middle)
+            .with(MouseButton::X2);
+        assert_eq!(buttons_mask(&buttons), 0b10110);
+    }
+
+    #[test]
+    fn packs_modifier_keys() {
+        assert_eq!(modifiers_mask(&Modifiers::empty()), 0);
+        assert_eq!(modifiers_mask(&Modifiers::SHIFT), MODIFIER_SHIFT);
+        assert_eq!(modifiers_mask(&Modifiers::CONTROL), MODIFIER_CTRL);
+        assert_eq!(modifiers_mask(&Modifiers::ALT), MODIFIER_ALT);
+        assert_eq!(modifiers_mask(&Modifiers::META), MODIFIER_META);
+        assert_eq!(
+            modifiers_mask(&(Modifiers::CONTROL | Modifiers::SHIFT | Modifiers::META)),
+            0b1011
+        );
+    }
+
+    #[test]
+    fn zooms_with_ctrl_and_pans_otherwise() {
+        let ctrl = Modifiers::CONTROL;
+        let zoom = |delta_y: f64| {
+            match wheel_gesture(Vec2::new(0., delta_y), &ctrl) {
+                WheelGesture::Zoom(factor) => factor,
+                gesture => panic!("expected a zoom, got {:?}", gesture),
+            }
+        };
+
+        assert_eq!(zoom(0.), 1.);
+        assert!((zoom(-WHEEL_ZOOM_DISTANCE) - std::f64::consts::E).abs() < 1e-9);
+        assert!((zoom(WHEEL_ZOOM_DISTANCE) * std::f64::consts::E - 1.).abs() < 1e-9);
+        // Zooming in and out by the same distance gets back to the same scale.
+        assert!((zoom(120.) * zoom(-120.) - 1.).abs() < 1e-9);
+
+        assert_eq!(
+            wheel_gesture(Vec2::new(3., 4.), &Modifiers::empty()),
+            WheelGesture::Pan(Vec2::new(-3., -4.))
+        );
+    }
+
+    #[test]
+    fn pans_horizontally_with_shift() {
+        let shift = Modifiers::SHIFT;
+
+        assert_eq!(
+            wheel_gesture(Vec2::new(0., 10.), &shift),
+            WheelGesture::Pan(Vec2::new(-10., 0.))
+        );
+        // Trackpads already scroll horizontally, their deltas are kept.
+        assert_eq!(
+            wheel_gesture(Vec2::new(5., 10.), &shift),
+            WheelGesture::Pan(Vec2::new(-5., -10.))
+        );
+    }
+}
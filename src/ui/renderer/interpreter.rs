@@ -0,0 +1,1926 @@
+use std::time::Duration;
+
+use druid::kurbo::{Affine, Arc, BezPath, Circle, Ellipse, Line, Shape, Vec2};
+use druid::{Color, Point, Rect, Size};
+
+use tech_paws_core as core;
+
+use super::backend::RenderBackend;
+use super::batch::{Batch, BatchKind};
+use super::camera::{self, Camera, Cameras};
+use super::canvas::{Background, CanvasStyle};
+use super::commands::{self, DecodeError, ExecOp, RenderOp};
+use super::diagnostics::{Arguments, Diagnostic, Pass};
+use super::grid::{Grid, GridStyle};
+use super::images::{ImageAtlas, ImageSource};
+use super::recorder::{Frame, Recorder};
+use super::stroke::{self, Stroke, DEFAULT_STROKE_WIDTH};
+use super::text::{
+    FontFamily, HorizontalAlignment, TextMetrics, TextStyle, VerticalAlignment, DEFAULT_FONT_SIZE,
+    DEFAULT_FONT_WEIGHT,
+};
+use super::viewport::Viewport;
+
+const DEFAULT_POINT_SIZE: f64 = 4.;
+
+/// The marker drawn for every point of `DrawPoints`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PointShape {
+    Round,
+    Square,
+}
+
+impl PointShape {
+    fn from_i32(value: i32) -> Self {
+        match value {
+            1 => PointShape::Square,
+            _ => PointShape::Round,
+        }
+    }
+}
+
+/// Whether `DrawEllipses` and `DrawRoundedRects` fill or outline their shapes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PaintMode {
+    Fill,
+    Stroke,
+}
+
+impl PaintMode {
+    fn from_i32(value: i32) -> Self {
+        match value {
+            1 => PaintMode::Stroke,
+            _ => PaintMode::Fill,
+        }
+    }
+}
+
+const PATH_MOVE_TO: i32 = 0;
+const PATH_LINE_TO: i32 = 1;
+const PATH_QUAD_TO: i32 = 2;
+const PATH_CURVE_TO: i32 = 3;
+const PATH_CLOSE: i32 = 4;
+
+/// Maximum distance of the flattened clip shapes from the exact ones.
+const CLIP_TOLERANCE: f64 = 0.1;
+
+/// Interprets the command streams of the core and draws them into a [`RenderBackend`].
+///
+/// Arguments are pushed onto typed stacks by the `Push*` commands and consumed by the
+/// following draw or state command. Malformed commands never panic: they are skipped and,
+/// when validation is enabled, reported to the log with the frame number and command index.
+pub struct Interpreter {
+    cameras: Cameras,
+    current_color: core::commands::Color,
+    text_style: TextStyle,
+    stroke: Stroke,
+    /// The local transforms pushed by the core, each one already combined with its parent.
+    transforms: Vec<Affine>,
+    /// The number of clips pushed by the core, each one is a saved state of the backend.
+    clip_depth: usize,
+    images: ImageAtlas,
+    /// Lines or quads waiting for more primitives with the same paint.
+    batch: Option<Batch>,
+    batching: bool,
+    /// The canvas the grid covers, lines are snapped to its device pixels.
+    viewport: Viewport,
+    grid: Option<Grid>,
+    /// The grid spacing last reported to the core for snapping.
+    snap_spacing: Option<f64>,
+    /// The theme of the canvas, for its background and the grid colors.
+    canvas: CanvasStyle,
+    /// The canvas background set by the core, it replaces the one of the theme.
+    background: Option<Background>,
+
+    current_camera_id: i32,
+    int32_data: Vec<i32>,
+    float32_data: Vec<f32>,
+    vec2f_data: Vec<core::commands::Vec2f>,
+    vec2i_data: Vec<core::commands::Vec2i>,
+    color_data: Vec<core::commands::Color>,
+    str_data: Vec<String>,
+    bytes_data: Vec<Vec<u8>>,
+
+    validate: bool,
+    frame_number: u64,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            current_camera_id: 0,
+            cameras: Cameras::new(),
+            int32_data: Vec::new(),
+            float32_data: Vec::new(),
+            vec2f_data: Vec::new(),
+            vec2i_data: Vec::new(),
+            color_data: Vec::new(),
+            str_data: Vec::new(),
+            bytes_data: Vec::new(),
+            current_color: core::commands::Color::rgb(0., 0., 0.),
+            text_style: TextStyle::default(),
+            stroke: Stroke::default(),
+            transforms: Vec::new(),
+            clip_depth: 0,
+            images: ImageAtlas::new(),
+            batch: None,
+            batching: true,
+            viewport: Viewport::new(Size::ZERO, Point::ORIGIN, 1.),
+            grid: None,
+            snap_spacing: None,
+            canvas: CanvasStyle::default(),
+            background: None,
+            validate: false,
+            frame_number: 0,
+        }
+    }
+
+    /// Enables logging of malformed commands: missing arguments, arguments left unused
+    /// by a command and command types the frontend doesn't know.
+    pub fn set_validation(&mut self, validate: bool) {
+        self.validate = validate;
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+
+    pub fn set_canvas_style(&mut self, canvas: CanvasStyle) {
+        self.canvas = canvas;
+    }
+
+    /// Whether registered images are still being decoded, the canvas has to be painted again
+    /// once they are ready.
+    pub fn is_loading_images(&self) -> bool {
+        self.images.is_loading()
+    }
+
+    /// Blocks until the registered images are decoded, for rendering without a window.
+    pub fn wait_for_images(&mut self, timeout: Duration) {
+        self.images.wait(timeout);
+    }
+
+    /// Enables merging the lines and quads of consecutive commands into single draw calls,
+    /// on by default.
+    pub fn set_batching(&mut self, batching: bool) {
+        self.batching = batching;
+    }
+
+    /// Runs a whole core frame: the given number of simulation steps, the measurement pass
+    /// and the render pass.
+    ///
+    /// The render passes draw the world as left by the last step. When a recorder is given,
+    /// all commands of the frame are appended to it.
+    pub fn frame<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        steps: usize,
+        recorder: Option<&mut Recorder>,
+    ) {
+        let mut recorded = recorder.as_ref().map(|_| Frame::default());
+
+        tech_paws_core::frame_start();
+
+        for _ in 0..steps {
+            tech_paws_core::step();
+            self.handle_exec_commands(exec_ops().inspect(|op| {
+                if let (Some(frame), op) = (&mut recorded, op) {
+                    frame.exec.push(op.clone());
+                }
+            }));
+            tech_paws_core::flush();
+        }
+
+        tech_paws_core::render_pass1();
+        let text_metrics = self.handle_render_state(
+            backend,
+            render_ops().inspect(|op| {
+                if let (Some(frame), op) = (&mut recorded, op) {
+                    frame.state.push(op.clone());
+                }
+            }),
+        );
+        self.flush();
+
+        for metrics in &text_metrics {
+            core::push_text_metrics(core_text_metrics(metrics));
+        }
+
+        tech_paws_core::flush();
+        tech_paws_core::render_pass2();
+
+        self.flush();
+        tech_paws_core::render_state_flush();
+
+        self.draw_background(backend);
+        self.draw_grid(backend);
+        self.push_snap_spacing();
+        self.handle_render_commands(
+            backend,
+            render_ops().inspect(|op| {
+                if let (Some(frame), op) = (&mut recorded, op) {
+                    frame.render.push(op.clone());
+                }
+            }),
+        );
+
+        tech_paws_core::frame_end();
+        self.frame_number += 1;
+
+        if let (Some(recorder), Some(frame)) = (recorder, recorded) {
+            if let Err(err) = recorder.write_frame(&frame) {
+                log::error!("Failed to record frame: {}", err);
+            }
+        }
+    }
+
+    /// Plays a recorded frame through the same handlers without stepping the core.
+    ///
+    /// Text metrics from the measurement pass are dropped since there is no core to consume them.
+    pub fn replay_frame<B: RenderBackend>(&mut self, backend: &mut B, frame: &Frame) {
+        self.handle_exec_commands(frame.exec.iter().cloned());
+
+        self.handle_render_state(backend, frame.state.iter().cloned());
+        self.flush();
+
+        self.draw_background(backend);
+        self.draw_grid(backend);
+        self.handle_render_commands(backend, frame.render.iter().cloned());
+        self.flush();
+
+        self.frame_number += 1;
+    }
+
+    pub fn handle_exec_commands(
+        &mut self,
+        ops: impl IntoIterator<Item = Result<ExecOp, DecodeError>>,
+    ) {
+        for (index, op) in ops.into_iter().enumerate() {
+            let result = match op {
+                Ok(ExecOp::PushVec2f(vec)) => {
+                    self.vec2f_data.push(vec);
+                    Ok(())
+                }
+                Ok(ExecOp::PushInt32(value)) => {
+                    self.int32_data.push(value);
+                    Ok(())
+                }
+                Ok(ExecOp::PushFloat32(value)) => {
+                    self.float32_data.push(value);
+                    Ok(())
+                }
+                Ok(ExecOp::UpdateCameraPosition) => {
+                    let consumed = self.update_camera_position();
+                    self.finish("UpdateCameraPosition", consumed)
+                }
+                Ok(ExecOp::UpdateCameraTransform) => {
+                    let consumed = self.update_camera_transform();
+                    self.finish("UpdateCameraTransform", consumed)
+                }
+                Err(err) => Err(Diagnostic::Decode(err)),
+            };
+
+            if let Err(diagnostic) = result {
+                self.report(Pass::Exec, index, diagnostic);
+            }
+        }
+    }
+
+    pub fn handle_render_commands<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        ops: impl IntoIterator<Item = Result<RenderOp, DecodeError>>,
+    ) {
+        let mut count = 0;
+
+        self.images.receive();
+
+        for (index, op) in ops.into_iter().enumerate() {
+            count = index + 1;
+
+            if op.as_ref().map_or(true, breaks_batch) {
+                self.flush_batch(backend);
+            }
+
+            let result = match op {
+                Ok(RenderOp::DrawText) => {
+                    let consumed = self.draw_text(backend);
+                    self.finish("DrawText", consumed)
+                }
+                Ok(RenderOp::DrawLines) => {
+                    let consumed = self.draw_lines(backend);
+                    self.finish("DrawLines", consumed)
+                }
+                Ok(RenderOp::DrawQuads) => {
+                    let consumed = self.draw_quads(backend);
+                    self.finish("DrawQuads", consumed)
+                }
+                Ok(RenderOp::DrawPoints) => {
+                    let consumed = self.draw_points(backend);
+                    self.finish("DrawPoints", consumed)
+                }
+                Ok(RenderOp::StrokePath) => {
+                    let consumed = self.draw_path(backend, "StrokePath", PaintMode::Stroke);
+                    self.finish("StrokePath", consumed)
+                }
+                Ok(RenderOp::FillPath) => {
+                    let consumed = self.draw_path(backend, "FillPath", PaintMode::Fill);
+                    self.finish("FillPath", consumed)
+                }
+                Ok(RenderOp::DrawArcs) => {
+                    let consumed = self.draw_arcs(backend);
+                    self.finish("DrawArcs", consumed)
+                }
+                Ok(RenderOp::DrawEllipses) => {
+                    let consumed = self.draw_ellipses(backend);
+                    self.finish("DrawEllipses", consumed)
+                }
+                Ok(RenderOp::DrawRoundedRects) => {
+                    let consumed = self.draw_rounded_rects(backend);
+                    self.finish("DrawRoundedRects", consumed)
+                }
+                Ok(RenderOp::DrawImage) => {
+                    let consumed = self.draw_image(backend);
+                    self.finish("DrawImage", consumed)
+                }
+                Ok(RenderOp::ReleaseImage) => {
+                    let consumed = self.release_image(backend);
+                    self.finish("ReleaseImage", consumed)
+                }
+                Ok(RenderOp::PushClipRect) => {
+                    let consumed = self.push_clip_rect(backend);
+                    self.finish("PushClipRect", consumed)
+                }
+                Ok(RenderOp::PopClip) => {
+                    let consumed = self.pop_clip(backend);
+                    self.finish("PopClip", consumed)
+                }
+                Ok(RenderOp::PushTransform) => {
+                    let consumed = self.push_transform();
+                    self.finish("PushTransform", consumed)
+                }
+                Ok(RenderOp::PopTransform) => {
+                    let consumed = self.pop_transform();
+                    self.finish("PopTransform", consumed)
+                }
+                Ok(RenderOp::SetCamera) => self.set_camera(),
+                Ok(RenderOp::SetColorUniform) => self.set_color_uniform(),
+                Ok(RenderOp::SetTextStyle) => self.set_text_style(),
+                Ok(RenderOp::SetStrokeStyle) => self.set_stroke_style(),
+                Ok(RenderOp::RegisterImage)
+                | Ok(RenderOp::SetCanvasBackground)
+                | Ok(RenderOp::SetGrid) => {
+                    self.flush();
+                    Ok(())
+                }
+                Ok(op) => {
+                    self.push_argument(op);
+                    Ok(())
+                }
+                Err(err) => Err(Diagnostic::Decode(err)),
+            };
+
+            if let Err(diagnostic) = result {
+                self.report(Pass::Render, index, diagnostic);
+            }
+        }
+
+        self.flush_batch(backend);
+
+        if let Err(diagnostic) = self.close_layers(backend) {
+            self.report(Pass::Render, count, diagnostic);
+        }
+    }
+
+    /// Runs the measurement pass and returns the metrics of the measured strings.
+    ///
+    /// There is one entry for every string of every `DrawText`, in the order the strings
+    /// were pushed. Only text is measured, the arguments of the other draw commands are
+    /// discarded.
+    pub fn handle_render_state<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        ops: impl IntoIterator<Item = Result<RenderOp, DecodeError>>,
+    ) -> Vec<TextMetrics> {
+        let mut text_metrics = Vec::new();
+
+        for (index, op) in ops.into_iter().enumerate() {
+            let result = match op {
+                Ok(RenderOp::DrawText) => {
+                    let consumed = self.render_state_text(backend, &mut text_metrics);
+                    self.finish("DrawText", consumed)
+                }
+                Ok(RenderOp::DrawLines)
+                | Ok(RenderOp::DrawQuads)
+                | Ok(RenderOp::DrawPoints)
+                | Ok(RenderOp::StrokePath)
+                | Ok(RenderOp::FillPath)
+                | Ok(RenderOp::DrawArcs)
+                | Ok(RenderOp::DrawEllipses)
+                | Ok(RenderOp::DrawRoundedRects)
+                | Ok(RenderOp::PushClipRect)
+                | Ok(RenderOp::PopClip)
+                | Ok(RenderOp::PushTransform)
+                | Ok(RenderOp::PopTransform)
+                | Ok(RenderOp::DrawImage)
+                | Ok(RenderOp::ReleaseImage) => {
+                    self.flush();
+                    Ok(())
+                }
+                Ok(RenderOp::RegisterImage) => {
+                    let consumed = self.register_image(backend);
+                    self.finish("RegisterImage", consumed)
+                }
+                Ok(RenderOp::SetCanvasBackground) => {
+                    let consumed = self.set_canvas_background();
+                    self.finish("SetCanvasBackground", consumed)
+                }
+                Ok(RenderOp::SetGrid) => {
+                    let consumed = self.set_grid();
+                    self.finish("SetGrid", consumed)
+                }
+                Ok(RenderOp::SetCamera) => self.set_camera(),
+                Ok(RenderOp::SetTextStyle) => self.set_text_style(),
+                Ok(RenderOp::SetStrokeStyle) => self.set_stroke_style(),
+                Ok(RenderOp::SetColorUniform) => self.set_color_uniform(),
+                Ok(op) => {
+                    self.push_argument(op);
+                    Ok(())
+                }
+                Err(err) => Err(Diagnostic::Decode(err)),
+            };
+
+            if let Err(diagnostic) = result {
+                self.report(Pass::Measure, index, diagnostic);
+            }
+        }
+
+        text_metrics
+    }
+
+    fn push_argument(&mut self, op: RenderOp) {
+        match op {
+            RenderOp::PushColor(color) => self.color_data.push(color),
+            RenderOp::PushVec2f(vec) => self.vec2f_data.push(vec),
+            RenderOp::PushInt32(value) => self.int32_data.push(value),
+            RenderOp::PushFloat32(value) => self.float32_data.push(value),
+            RenderOp::PushString(str) => self.str_data.push(str),
+            RenderOp::PushBytes(bytes) => self.bytes_data.push(bytes),
+            _ => (),
+        }
+    }
+
+    fn set_camera(&mut self) -> Result<(), Diagnostic> {
+        let result = match self.int32_data.first() {
+            Some(camera_id) => {
+                self.current_camera_id = *camera_id;
+                leftovers(
+                    "SetCamera",
+                    Arguments {
+                        int32: self.int32_data.len() - 1,
+                        ..Arguments::default()
+                    },
+                )
+            }
+            None => Err(missing("SetCamera", "camera id")),
+        };
+
+        self.int32_data.clear();
+        result
+    }
+
+    fn set_color_uniform(&mut self) -> Result<(), Diagnostic> {
+        let result = match self.color_data.first() {
+            Some(color) => {
+                self.current_color = *color;
+                leftovers(
+                    "SetColorUniform",
+                    Arguments {
+                        color: self.color_data.len() - 1,
+                        ..Arguments::default()
+                    },
+                )
+            }
+            None => Err(missing("SetColorUniform", "color")),
+        };
+
+        self.color_data.clear();
+        result
+    }
+
+    /// Replaces the canvas background of the theme until the next `SetCanvasBackground`.
+    ///
+    /// Takes one color for a solid background or the top and bottom colors of a vertical
+    /// gradient; without colors the background of the theme is restored. The background is
+    /// set by the measurement pass and painted before the render pass of the same frame.
+    fn set_canvas_background(&mut self) -> Result<Arguments, Diagnostic> {
+        self.background = match self.color_data.as_slice() {
+            [] => None,
+            [color] => Some(Background::Solid(druid_color(color))),
+            [top, bottom, ..] => {
+                Some(Background::from_colors(
+                    druid_color(top),
+                    druid_color(bottom),
+                ))
+            }
+        };
+
+        Ok(Arguments {
+            color: self.color_data.len().min(2),
+            ..Arguments::default()
+        })
+    }
+
+    /// Shows the background grid until the next `SetGrid`, seen through the current camera.
+    ///
+    /// Takes the spacing of the minor lines in scheme units as float32, then the number of
+    /// minor lines per major line and the [`GridStyle`] as int32: 0 draws lines, 1 dots.
+    /// Without a spacing the grid is hidden. The grid is set by the measurement pass, so it's
+    /// drawn by the render pass of the same frame.
+    fn set_grid(&mut self) -> Result<Arguments, Diagnostic> {
+        let spacing = match self.float32_data.first() {
+            Some(spacing) if *spacing > 0. && spacing.is_finite() => *spacing as f64,
+            Some(_) => {
+                self.grid = None;
+                return Err(invalid("SetGrid", "spacing"));
+            }
+            None => {
+                self.grid = None;
+                return Ok(Arguments::default());
+            }
+        };
+
+        let mut ints = self.int32_data.iter().copied();
+        let major_every = ints.next();
+        let style = ints
+            .next()
+            .map(GridStyle::from_i32)
+            .unwrap_or(GridStyle::Lines);
+
+        self.grid = Some(Grid::new(
+            spacing,
+            major_every,
+            style,
+            self.current_camera_id,
+        ));
+
+        Ok(Arguments {
+            float32: 1,
+            int32: self.int32_data.len().min(2),
+            ..Arguments::default()
+        })
+    }
+
+    /// Paints the background of the canvas under the grid.
+    fn draw_background<B: RenderBackend>(&self, backend: &mut B) {
+        self.canvas
+            .paint_background(backend, self.viewport.size, self.background.as_ref());
+    }
+
+    /// Draws the grid under the primitives of the render pass.
+    fn draw_grid<B: RenderBackend>(&self, backend: &mut B) {
+        if let Some(grid) = &self.grid {
+            let transform = self.cameras.get(grid.camera_id).transform();
+
+            grid.draw(backend, transform, &self.viewport, &self.canvas.grid);
+        }
+    }
+
+    /// Tells the core the spacing of the drawn grid when it changes, so objects snap to the
+    /// lines the user sees at the current zoom rather than to the finest level; zero when
+    /// there is no grid. The core takes it through `push_set_grid_spacing_request_command`.
+    fn push_snap_spacing(&mut self) {
+        let spacing = self
+            .grid
+            .as_ref()
+            .and_then(|grid| grid.snap_spacing(self.cameras.get(grid.camera_id).transform()));
+
+        if spacing != self.snap_spacing {
+            core::push_set_grid_spacing_request_command(spacing.unwrap_or(0.) as f32);
+            self.snap_spacing = spacing;
+        }
+    }
+
+    /// Sets the text style of the next `DrawText`.
+    ///
+    /// Takes an optional family name as string, the font size and the wrapping width
+    /// as float32, then the weight, italic flag, horizontal and vertical alignment as int32;
+    /// missing values keep their defaults and a non-positive width disables wrapping.
+    fn set_text_style(&mut self) -> Result<(), Diagnostic> {
+        let mut ints = self.int32_data.iter().copied();
+
+        self.text_style = TextStyle {
+            family: self
+                .str_data
+                .last()
+                .map(|name| FontFamily::from_name(name))
+                .unwrap_or(FontFamily::SystemUi),
+            size: self
+                .float32_data
+                .first()
+                .map(|size| *size as f64)
+                .filter(|size| *size > 0.)
+                .unwrap_or(DEFAULT_FONT_SIZE),
+            weight: ints
+                .next()
+                .map(|weight| weight.max(1).min(1000) as u16)
+                .unwrap_or(DEFAULT_FONT_WEIGHT),
+            italic: ints.next().map(|italic| italic != 0).unwrap_or(false),
+            horizontal_alignment: ints
+                .next()
+                .map(HorizontalAlignment::from_i32)
+                .unwrap_or(HorizontalAlignment::Left),
+            vertical_alignment: ints
+                .next()
+                .map(VerticalAlignment::from_i32)
+                .unwrap_or(VerticalAlignment::Top),
+            max_width: self
+                .float32_data
+                .get(1)
+                .map(|width| *width as f64)
+                .filter(|width| *width > 0.),
+        };
+
+        let unused = Arguments {
+            int32: ints.len(),
+            float32: self.float32_data.len().saturating_sub(2),
+            string: self.str_data.len().saturating_sub(1),
+            ..Arguments::default()
+        };
+
+        self.int32_data.clear();
+        self.float32_data.clear();
+        self.str_data.clear();
+        leftovers("SetTextStyle", unused)
+    }
+
+    /// Sets the stroke of the next stroked primitives.
+    ///
+    /// Takes the width, the dash offset and then the lengths of the dashes and gaps as
+    /// float32, the line cap (0 butt, 1 round, 2 square) and the line join (0 miter,
+    /// 1 round, 2 bevel) as int32. Without dash lengths the stroke is solid.
+    fn set_stroke_style(&mut self) -> Result<(), Diagnostic> {
+        let mut floats = self.float32_data.iter().map(|value| *value as f64);
+        let mut ints = self.int32_data.iter().copied();
+
+        let width = floats.next().unwrap_or(DEFAULT_STROKE_WIDTH);
+        let dash_offset = floats.next().unwrap_or(0.);
+        let dash: Vec<f64> = floats.collect();
+        let cap = ints
+            .next()
+            .map(stroke::line_cap_from_i32)
+            .unwrap_or(Stroke::default().cap);
+        let join = ints
+            .next()
+            .map(stroke::line_join_from_i32)
+            .unwrap_or(Stroke::default().join);
+        let unused = Arguments {
+            int32: ints.len(),
+            ..Arguments::default()
+        };
+
+        self.int32_data.clear();
+        self.float32_data.clear();
+
+        if width <= 0. {
+            return Err(invalid("SetStrokeStyle", "width"));
+        }
+
+        // Negative lengths are invalid and a pattern of zero length can't be drawn.
+        let dash_length: f64 = dash.iter().sum();
+
+        if dash.iter().any(|length| *length < 0.) || (!dash.is_empty() && dash_length <= 0.) {
+            return Err(invalid("SetStrokeStyle", "dash array"));
+        }
+
+        self.stroke = Stroke {
+            width,
+            dash,
+            dash_offset,
+            cap,
+            join,
+        };
+
+        leftovers("SetStrokeStyle", unused)
+    }
+
+    fn render_state_text<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        metrics: &mut Vec<TextMetrics>,
+    ) -> Result<Arguments, Diagnostic> {
+        let color = self.color();
+
+        for str in &self.str_data {
+            metrics.push(backend.measure_text(str, &self.text_style, &color));
+        }
+
+        Ok(self.text_arguments())
+    }
+
+    fn draw_text<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let consumed = self.text_arguments();
+        let color = self.color();
+        self.begin_camera(backend);
+
+        // Strings and positions are paired from the top of their stacks.
+        let mut positions = self.vec2f_data.iter().rev();
+
+        for str in self.str_data.iter().rev() {
+            let pos = positions
+                .next()
+                .map(|vec| Point::new(vec.x as f64, vec.y as f64))
+                .unwrap_or(Point::ORIGIN);
+
+            backend.draw_text(str, pos, &self.text_style, &color);
+        }
+
+        backend.restore();
+        Ok(consumed)
+    }
+
+    /// Text commands take every pushed string and a position for each of them.
+    fn text_arguments(&self) -> Arguments {
+        Arguments {
+            string: self.str_data.len(),
+            vec2f: self.vec2f_data.len().min(self.str_data.len()),
+            ..Arguments::default()
+        }
+    }
+
+    /// Strokes a line between every pair of pushed vec2f.
+    ///
+    /// The lines are added to the current batch, consecutive commands with the same opaque
+    /// color, stroke and transform are drawn with a single stroke.
+    fn draw_lines<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let transform = self.transform();
+        let kind = BatchKind::Lines(self.stroke.clone());
+        let mut batch = self.take_batch(backend, kind, transform);
+        let snap = |vec: core::commands::Vec2f| {
+            let point = Point::new(vec.x as f64, vec.y as f64);
+            camera::snap_to_pixel(transform, &self.viewport, self.stroke.width, point)
+        };
+
+        for chunk in self.vec2f_data.chunks_exact(2) {
+            batch.add_line(Line::new(snap(chunk[0]), snap(chunk[1])));
+        }
+
+        self.put_batch(backend, batch);
+        Ok(pairs(self.vec2f_data.len()))
+    }
+
+    /// Fills a rectangle for every pair of pushed vec2f, its origin and size; batched like
+    /// the lines.
+    fn draw_quads<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let mut batch = self.take_batch(backend, BatchKind::Quads, self.transform());
+
+        for chunk in self.vec2f_data.chunks_exact(2) {
+            let pos = chunk[0];
+            let size = chunk[1];
+
+            batch.add_rect(Rect::from_origin_size(
+                (pos.x as f64, pos.y as f64),
+                (size.x as f64, size.y as f64),
+            ));
+        }
+
+        self.put_batch(backend, batch);
+        Ok(pairs(self.vec2f_data.len()))
+    }
+
+    /// Returns the current batch if the primitives of the command can join it, otherwise
+    /// draws it and starts a new one.
+    fn take_batch<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        kind: BatchKind,
+        transform: Affine,
+    ) -> Batch {
+        let color = self.color();
+
+        match self.batch.take() {
+            Some(batch) if batch.accepts(&kind, &color, transform) => batch,
+            Some(batch) => {
+                batch.draw(backend);
+                Batch::new(kind, &color, transform)
+            }
+            None => Batch::new(kind, &color, transform),
+        }
+    }
+
+    /// Keeps the batch open for the following commands, or draws it right away when
+    /// batching is disabled or its color is translucent.
+    fn put_batch<B: RenderBackend>(&mut self, backend: &mut B, batch: Batch) {
+        if self.batching && batch.is_opaque() {
+            self.batch = Some(batch);
+        }
+        else {
+            batch.draw(backend);
+        }
+    }
+
+    fn flush_batch<B: RenderBackend>(&mut self, backend: &mut B) {
+        if let Some(batch) = self.batch.take() {
+            batch.draw(backend);
+        }
+    }
+
+    /// Draws a marker at every pushed vec2f.
+    ///
+    /// The marker size is the first pushed float32 or, if there is none, the first pushed int32.
+    /// The int32 following the size selects the [`PointShape`]: 0 is round, 1 is square.
+    fn draw_points<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let mut consumed = Arguments {
+            vec2f: self.vec2f_data.len(),
+            ..Arguments::default()
+        };
+
+        let mut ints = self.int32_data.iter().copied();
+        let size = match self.float32_data.first() {
+            Some(size) => {
+                consumed.float32 = 1;
+                *size as f64
+            }
+            None => {
+                ints.next()
+                    .map(|size| size as f64)
+                    .unwrap_or(DEFAULT_POINT_SIZE)
+            }
+        };
+        let shape = ints
+            .next()
+            .map(PointShape::from_i32)
+            .unwrap_or(PointShape::Round);
+        consumed.int32 = self.int32_data.len() - ints.len();
+
+        let color = self.color();
+        self.begin_camera(backend);
+
+        for point in &self.vec2f_data {
+            let center = Point::new(point.x as f64, point.y as f64);
+
+            match shape {
+                PointShape::Round => backend.fill_circle(Circle::new(center, size / 2.), &color),
+                PointShape::Square => {
+                    backend.fill_quad(Rect::from_center_size(center, (size, size)), &color)
+                }
+            }
+        }
+
+        backend.restore();
+        Ok(consumed)
+    }
+
+    /// Strokes or fills a path.
+    ///
+    /// The int32 arguments are the verbs of the path: 0 moves to, 1 draws a line to,
+    /// 2 a quadratic and 3 a cubic Bézier curve to the next vec2f points, 4 closes
+    /// the current subpath. Moves, lines, quadratic and cubic curves take 1, 1, 2 and
+    /// 3 points respectively, and the path has to start with a move.
+    fn draw_path<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+        command: &'static str,
+        mode: PaintMode,
+    ) -> Result<Arguments, Diagnostic> {
+        if self
+            .int32_data
+            .first()
+            .map_or(false, |verb| *verb != PATH_MOVE_TO)
+        {
+            return Err(invalid(command, "path start"));
+        }
+
+        let mut path = BezPath::new();
+        let mut points = self
+            .vec2f_data
+            .iter()
+            .map(|vec| Point::new(vec.x as f64, vec.y as f64));
+        let mut point = || points.next().ok_or_else(|| missing(command, "path point"));
+
+        for verb in &self.int32_data {
+            match *verb {
+                PATH_MOVE_TO => path.move_to(point()?),
+                PATH_LINE_TO => path.line_to(point()?),
+                PATH_QUAD_TO => path.quad_to(point()?, point()?),
+                PATH_CURVE_TO => path.curve_to(point()?, point()?, point()?),
+                PATH_CLOSE => path.close_path(),
+                _ => return Err(invalid(command, "path verb")),
+            }
+        }
+
+        let consumed = Arguments {
+            int32: self.int32_data.len(),
+            vec2f: self.vec2f_data.len() - points.len(),
+            ..Arguments::default()
+        };
+
+        let color = self.color();
+        self.begin_camera(backend);
+
+        match mode {
+            PaintMode::Fill => backend.fill_shape(&path, &color),
+            PaintMode::Stroke => backend.stroke_shape(&path, &color, &self.stroke),
+        }
+
+        backend.restore();
+        Ok(consumed)
+    }
+
+    /// Strokes elliptical arcs.
+    ///
+    /// Every arc takes its center and radii as vec2f and its start and sweep angles
+    /// in radians as float32.
+    fn draw_arcs<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let count = (self.vec2f_data.len() / 2).min(self.float32_data.len() / 2);
+        let color = self.color();
+        self.begin_camera(backend);
+
+        for index in 0..count {
+            let center = self.vec2f_data[index * 2];
+            let radii = self.vec2f_data[index * 2 + 1];
+
+            let arc = Arc {
+                center: Point::new(center.x as f64, center.y as f64),
+                radii: Vec2::new(radii.x as f64, radii.y as f64),
+                start_angle: self.float32_data[index * 2] as f64,
+                sweep_angle: self.float32_data[index * 2 + 1] as f64,
+                x_rotation: 0.,
+            };
+            backend.stroke_shape(&arc, &color, &self.stroke);
+        }
+
+        backend.restore();
+        Ok(Arguments {
+            vec2f: count * 2,
+            float32: count * 2,
+            ..Arguments::default()
+        })
+    }
+
+    /// Draws ellipses, every one takes its center and radii as vec2f.
+    ///
+    /// An optional int32 selects the [`PaintMode`]: 0 fills, 1 strokes.
+    fn draw_ellipses<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+    ) -> Result<Arguments, Diagnostic> {
+        let mode = self.paint_mode();
+        let color = self.color();
+        self.begin_camera(backend);
+
+        for chunk in self.vec2f_data.chunks_exact(2) {
+            let center = chunk[0];
+            let radii = chunk[1];
+
+            let ellipse = Ellipse::new(
+                (center.x as f64, center.y as f64),
+                (radii.x as f64, radii.y as f64),
+                0.,
+            );
+
+            match mode {
+                PaintMode::Fill => backend.fill_shape(&ellipse, &color),
+                PaintMode::Stroke => backend.stroke_shape(&ellipse, &color, &self.stroke),
+            }
+        }
+
+        backend.restore();
+        Ok(Arguments {
+            int32: self.int32_data.len().min(1),
+            ..pairs(self.vec2f_data.len())
+        })
+    }
+
+    /// Draws rounded rectangles, every one takes its origin and size as vec2f and its
+    /// corner radius as float32; rectangles without a radius get sharp corners.
+    ///
+    /// An optional int32 selects the [`PaintMode`]: 0 fills, 1 strokes.
+    fn draw_rounded_rects<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+    ) -> Result<Arguments, Diagnostic> {
+        let mode = self.paint_mode();
+        let color = self.color();
+        self.begin_camera(backend);
+
+        for (index, chunk) in self.vec2f_data.chunks_exact(2).enumerate() {
+            let pos = chunk[0];
+            let size = chunk[1];
+            let radius = self.float32_data.get(index).copied().unwrap_or(0.);
+
+            let rect = Rect::from_origin_size(
+                (pos.x as f64, pos.y as f64),
+                (size.x as f64, size.y as f64),
+            )
+            .to_rounded_rect(radius as f64);
+
+            match mode {
+                PaintMode::Fill => backend.fill_shape(&rect, &color),
+                PaintMode::Stroke => backend.stroke_shape(&rect, &color, &self.stroke),
+            }
+        }
+
+        backend.restore();
+        Ok(Arguments {
+            int32: self.int32_data.len().min(1),
+            float32: self.float32_data.len().min(self.vec2f_data.len() / 2),
+            ..pairs(self.vec2f_data.len())
+        })
+    }
+
+    /// Starts decoding an image stored under an id until the matching `ReleaseImage`.
+    ///
+    /// Takes the id as int32 and either the encoded image as bytes or the path of an image
+    /// file as string. The image is decoded in the background and drawn by the first render
+    /// pass after it is ready.
+    fn register_image<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+    ) -> Result<Arguments, Diagnostic> {
+        let id = *self
+            .int32_data
+            .first()
+            .ok_or_else(|| missing("RegisterImage", "image id"))?;
+
+        // The source is popped, so it isn't counted as a leftover.
+        let source = match self.bytes_data.pop() {
+            Some(bytes) => ImageSource::Bytes(bytes),
+            None => {
+                ImageSource::File(
+                    self.str_data
+                        .pop()
+                        .ok_or_else(|| missing("RegisterImage", "image data"))?
+                        .into(),
+                )
+            }
+        };
+
+        if let Some(replaced) = self.images.register(id, source) {
+            backend.release_image(&replaced);
+        }
+
+        Ok(Arguments {
+            int32: 1,
+            ..Arguments::default()
+        })
+    }
+
+    /// Forgets the images with the int32 ids and drops their backend copies.
+    fn release_image<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+    ) -> Result<Arguments, Diagnostic> {
+        if self.int32_data.is_empty() {
+            return Err(missing("ReleaseImage", "image id"));
+        }
+
+        for id in &self.int32_data {
+            if let Some(image) = self.images.release(*id) {
+                backend.release_image(&image);
+            }
+        }
+
+        Ok(Arguments {
+            int32: self.int32_data.len(),
+            ..Arguments::default()
+        })
+    }
+
+    /// Draws a registered image.
+    ///
+    /// Takes the id as int32, the origin and size of the destination rectangle as vec2f,
+    /// optionally followed by the origin and size of the drawn part of the image in pixels,
+    /// and an optional opacity as float32.
+    fn draw_image<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        let id = *self
+            .int32_data
+            .first()
+            .ok_or_else(|| missing("DrawImage", "image id"))?;
+        if !self.images.contains(id) {
+            return Err(invalid("DrawImage", "image id"));
+        }
+
+        let rect = |pos: core::commands::Vec2f, size: core::commands::Vec2f| {
+            Rect::from_origin_size((pos.x as f64, pos.y as f64), (size.x as f64, size.y as f64))
+        };
+
+        let (dest, source) = match self.vec2f_data.as_slice() {
+            [pos, size] => (rect(*pos, *size), None),
+            [pos, size, source_pos, source_size, ..] => {
+                (rect(*pos, *size), Some(rect(*source_pos, *source_size)))
+            }
+            _ => return Err(missing("DrawImage", "destination rect")),
+        };
+
+        let opacity = self.float32_data.first().copied().unwrap_or(1.);
+
+        if !(0. ..=1.).contains(&opacity) {
+            return Err(invalid("DrawImage", "opacity"));
+        }
+
+        // Images still loading are skipped.
+        if let Some(image) = self.images.get(id) {
+            self.begin_camera(backend);
+            backend.draw_image(image, dest, source, opacity as f64);
+            backend.restore();
+        }
+
+        Ok(Arguments {
+            int32: 1,
+            vec2f: if source.is_some() { 4 } else { 2 },
+            float32: self.float32_data.len().min(1),
+            ..Arguments::default()
+        })
+    }
+
+    fn paint_mode(&self) -> PaintMode {
+        self.int32_data
+            .first()
+            .map(|mode| PaintMode::from_i32(*mode))
+            .unwrap_or(PaintMode::Fill)
+    }
+
+    /// Applies the transform of the current camera and the pushed local transforms to the
+    /// following primitives and returns it; the caller restores the backend when it's done.
+    fn begin_camera<B: RenderBackend>(&self, backend: &mut B) -> Affine {
+        let transform = self.transform();
+
+        backend.save();
+        backend.transform(transform);
+        transform
+    }
+
+    /// The transform from the local coordinates of the primitives to the canvas.
+    fn transform(&self) -> Affine {
+        let local = self.transforms.last().copied().unwrap_or(Affine::IDENTITY);
+        self.cameras.get(self.current_camera_id).transform() * local
+    }
+
+    /// Clips the following primitives to a rectangle until the matching `PopClip`.
+    ///
+    /// Takes the origin and size of the rectangle in the current coordinates as vec2f
+    /// and an optional corner radius as float32.
+    fn push_clip_rect<B: RenderBackend>(
+        &mut self,
+        backend: &mut B,
+    ) -> Result<Arguments, Diagnostic> {
+        let (pos, size) = match self.vec2f_data.as_slice() {
+            [pos, size, ..] => (*pos, *size),
+            _ => return Err(missing("PushClipRect", "rect")),
+        };
+        let radius = self.float32_data.first().copied().unwrap_or(0.);
+
+        let rect =
+            Rect::from_origin_size((pos.x as f64, pos.y as f64), (size.x as f64, size.y as f64))
+                .to_rounded_rect(radius as f64);
+
+        // The clip stays while the camera and the transforms change, so it's set in canvas
+        // coordinates.
+        let shape = self.transform() * rect.to_path(CLIP_TOLERANCE);
+
+        backend.save();
+        backend.clip(&shape);
+        self.clip_depth += 1;
+
+        Ok(Arguments {
+            vec2f: 2,
+            float32: self.float32_data.len().min(1),
+            ..Arguments::default()
+        })
+    }
+
+    fn pop_clip<B: RenderBackend>(&mut self, backend: &mut B) -> Result<Arguments, Diagnostic> {
+        if self.clip_depth == 0 {
+            return Err(Diagnostic::UnbalancedPop { command: "PopClip" });
+        }
+
+        backend.restore();
+        self.clip_depth -= 1;
+        Ok(Arguments::default())
+    }
+
+    /// Sets a local coordinate system for the following primitives until the matching
+    /// `PopTransform`, nested in the current one.
+    ///
+    /// Takes the six coefficients `[a, b, c, d, e, f]` of the affine transform as float32,
+    /// a point `(x, y)` is mapped to `(a * x + c * y + e, b * x + d * y + f)`.
+    fn push_transform(&mut self) -> Result<Arguments, Diagnostic> {
+        if self.float32_data.len() < 6 {
+            return Err(missing("PushTransform", "transform coefficients"));
+        }
+
+        let mut coefficients = [0.; 6];
+
+        for (coefficient, value) in coefficients.iter_mut().zip(&self.float32_data) {
+            *coefficient = *value as f64;
+        }
+
+        let parent = self.transforms.last().copied().unwrap_or(Affine::IDENTITY);
+        let transform = Affine::new(coefficients);
+
+        // A singular matrix can't be inverted and breaks the backend. The parent is pushed
+        // instead, so the matching `PopTransform` stays balanced.
+        if transform.determinant() == 0. {
+            self.transforms.push(parent);
+            return Err(invalid("PushTransform", "transform coefficients"));
+        }
+
+        self.transforms.push(parent * transform);
+
+        Ok(Arguments {
+            float32: 6,
+            ..Arguments::default()
+        })
+    }
+
+    fn pop_transform(&mut self) -> Result<Arguments, Diagnostic> {
+        self.transforms
+            .pop()
+            .map(|_| Arguments::default())
+            .ok_or(Diagnostic::UnbalancedPop {
+                command: "PopTransform",
+            })
+    }
+
+    /// Pops the clips and transforms the core left pushed at the end of the render pass.
+    fn close_layers<B: RenderBackend>(&mut self, backend: &mut B) -> Result<(), Diagnostic> {
+        if self.clip_depth == 0 && self.transforms.is_empty() {
+            return Ok(());
+        }
+
+        let unclosed = Diagnostic::Unclosed {
+            clips: self.clip_depth,
+            transforms: self.transforms.len(),
+        };
+
+        for _ in 0..self.clip_depth {
+            backend.restore();
+        }
+
+        self.clip_depth = 0;
+        self.transforms.clear();
+        Err(unclosed)
+    }
+
+    fn update_camera_position(&mut self) -> Result<Arguments, Diagnostic> {
+        let camera_id = *self
+            .int32_data
+            .first()
+            .ok_or_else(|| missing("UpdateCameraPosition", "camera id"))?;
+        let position = *self
+            .vec2f_data
+            .first()
+            .ok_or_else(|| missing("UpdateCameraPosition", "position"))?;
+
+        self.cameras.get_mut(camera_id).position = Vec2::new(position.x as f64, position.y as f64);
+
+        Ok(Arguments {
+            int32: 1,
+            vec2f: 1,
+            ..Arguments::default()
+        })
+    }
+
+    /// Replaces the whole transform of a camera.
+    ///
+    /// Takes the camera id as int32, the position as vec2f, then the zoom and optionally
+    /// the rotation in radians as float32.
+    fn update_camera_transform(&mut self) -> Result<Arguments, Diagnostic> {
+        let camera_id = *self
+            .int32_data
+            .first()
+            .ok_or_else(|| missing("UpdateCameraTransform", "camera id"))?;
+        let position = *self
+            .vec2f_data
+            .first()
+            .ok_or_else(|| missing("UpdateCameraTransform", "position"))?;
+        let zoom = self.float32_data.first().copied().unwrap_or(1.);
+        let rotation = self.float32_data.get(1).copied().unwrap_or(0.);
+
+        // A camera without a positive zoom can't be inverted.
+        if zoom <= 0. {
+            return Err(invalid("UpdateCameraTransform", "zoom"));
+        }
+
+        *self.cameras.get_mut(camera_id) = Camera {
+            position: Vec2::new(position.x as f64, position.y as f64),
+            zoom: zoom as f64,
+            rotation: rotation as f64,
+        };
+
+        Ok(Arguments {
+            int32: 1,
+            vec2f: 1,
+            float32: self.float32_data.len().min(2),
+            ..Arguments::default()
+        })
+    }
+
+    fn color(&self) -> Color {
+        druid_color(&self.current_color)
+    }
+
+    fn arguments(&self) -> Arguments {
+        Arguments {
+            int32: self.int32_data.len(),
+            float32: self.float32_data.len(),
+            vec2f: self.vec2f_data.len(),
+            color: self.color_data.len(),
+            string: self.str_data.len(),
+            bytes: self.bytes_data.len(),
+        }
+    }
+
+    /// Finishes a command that consumes the whole argument stacks.
+    ///
+    /// Reports the arguments the command didn't use and flushes the stacks, so a failed
+    /// command doesn't leak its arguments into the next one.
+    fn finish(
+        &mut self,
+        command: &'static str,
+        consumed: Result<Arguments, Diagnostic>,
+    ) -> Result<(), Diagnostic> {
+        let result = consumed
+            .and_then(|consumed| leftovers(command, self.arguments().saturating_sub(consumed)));
+
+        self.flush();
+        result
+    }
+
+    fn report(&self, pass: Pass, index: usize, diagnostic: Diagnostic) {
+        if self.validate {
+            log::warn!(
+                "frame {}, {} command #{}: {}",
+                self.frame_number,
+                pass,
+                index,
+                diagnostic
+            );
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.current_color = core::commands::Color::rgb(0., 0., 0.);
+        self.text_style = TextStyle::default();
+        self.stroke = Stroke::default();
+        self.int32_data.clear();
+        self.float32_data.clear();
+        self.vec2f_data.clear();
+        self.vec2i_data.clear();
+        self.color_data.clear();
+        self.str_data.clear();
+        self.bytes_data.clear();
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+/// Decodes the current execution commands.
+fn exec_ops() -> impl Iterator<Item = Result<ExecOp, DecodeError>> {
+    let commands = commands::exec_commands()
+        .map_err(|err| log::error!("Failed to read execution commands: {}", err))
+        .ok();
+
+    commands.into_iter().flatten()
+}
+
+/// Decodes the current render commands.
+fn render_ops() -> impl Iterator<Item = Result<RenderOp, DecodeError>> {
+    let commands = commands::render_commands()
+        .map_err(|err| log::error!("Failed to read render commands: {}", err))
+        .ok();
+
+    commands.into_iter().flatten()
+}
+
+/// Converts the metrics into the payload of the measurement results of the core.
+///
+/// `push_text_metrics` replaces the `push_text_size` of the core, which only took the size.
+fn core_text_metrics(metrics: &TextMetrics) -> core::commands::TextMetrics {
+    core::commands::TextMetrics {
+        size: core::commands::Vec2f::new(metrics.size.width as f32, metrics.size.height as f32),
+        baseline: metrics.baseline as f32,
+        ascent: metrics.ascent as f32,
+        descent: metrics.descent as f32,
+        line_count: metrics.line_count as i32,
+        carets: metrics
+            .carets
+            .iter()
+            .map(|caret| core::commands::Vec2f::new(caret.x as f32, caret.y as f32))
+            .collect(),
+    }
+}
+
+fn druid_color(color: &core::commands::Color) -> Color {
+    Color::rgba(color.r, color.g, color.b, color.a)
+}
+
+fn missing(command: &'static str, argument: &'static str) -> Diagnostic {
+    Diagnostic::MissingArgument { command, argument }
+}
+
+fn invalid(command: &'static str, argument: &'static str) -> Diagnostic {
+    Diagnostic::InvalidArgument { command, argument }
+}
+
+fn leftovers(command: &'static str, arguments: Arguments) -> Result<(), Diagnostic> {
+    if arguments.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(Diagnostic::Leftovers { command, arguments })
+    }
+}
+
+/// Whether the command draws or changes the clip, so the pending batch must be drawn first
+/// to keep the painting order.
+fn breaks_batch(op: &RenderOp) -> bool {
+    matches!(
+        op,
+        RenderOp::DrawText
+            | RenderOp::DrawPoints
+            | RenderOp::StrokePath
+            | RenderOp::FillPath
+            | RenderOp::DrawArcs
+            | RenderOp::DrawEllipses
+            | RenderOp::DrawRoundedRects
+            | RenderOp::DrawImage
+            | RenderOp::PushClipRect
+            | RenderOp::PopClip
+    )
+}
+
+/// Commands taking pairs of vec2f ignore an unpaired last one.
+fn pairs(vec2f_count: usize) -> Arguments {
+    Arguments {
+        vec2f: vec2f_count - vec2f_count % 2,
+        ..Arguments::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid::kurbo::PathEl;
+    use druid::piet::{LineCap, LineJoin};
+
+    use super::*;
+    use crate::ui::renderer::images::Image;
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Fill,
+        Stroke,
+        Image,
+        Text(String),
+        Measure(String, u32),
+        Save,
+        Restore,
+        Clip,
+        Transform,
+    }
+
+    /// Records the calls of the interpreter instead of drawing.
+    #[derive(Default)]
+    struct RecordingBackend {
+        calls: Vec<Call>,
+        quads: Vec<Rect>,
+        circles: Vec<Circle>,
+        transforms: Vec<Affine>,
+        /// The elements and bounds of every stroked or filled shape.
+        paths: Vec<Vec<PathEl>>,
+        bounds: Vec<Rect>,
+        strokes: Vec<Stroke>,
+    }
+
+    impl RecordingBackend {
+        fn count(&self, call: &Call) -> usize {
+            self.calls
+                .iter()
+                .filter(|recorded| *recorded == call)
+                .count()
+        }
+
+        fn record_shape(&mut self, shape: &impl Shape) {
+            self.paths.push(shape.to_bez_path(0.1).collect());
+            self.bounds.push(shape.bounding_box());
+        }
+    }
+
+    impl RenderBackend for RecordingBackend {
+        fn fill_quad(&mut self, rect: Rect, _color: &Color) {
+            self.calls.push(Call::Fill);
+            self.quads.push(rect);
+        }
+
+        fn fill_circle(&mut self, circle: Circle, _color: &Color) {
+            self.calls.push(Call::Fill);
+            self.circles.push(circle);
+        }
+
+        fn stroke_shape(&mut self, shape: &impl Shape, _color: &Color, stroke: &Stroke) {
+            self.calls.push(Call::Stroke);
+            self.record_shape(shape);
+            self.strokes.push(stroke.clone());
+        }
+
+        fn fill_shape(&mut self, shape: &impl Shape, _color: &Color) {
+            self.calls.push(Call::Fill);
+            self.record_shape(shape);
+        }
+
+        fn fill_linear_gradient(
+            &mut self,
+            _shape: &impl Shape,
+            _start: (Point, &Color),
+            _end: (Point, &Color),
+        ) {
+            self.calls.push(Call::Fill);
+        }
+
+        fn draw_image(&mut self, _image: &Image, _dest: Rect, _source: Option<Rect>, _: f64) {
+            self.calls.push(Call::Image);
+        }
+
+        fn release_image(&mut self, _image: &Image) {}
+
+        fn draw_text(&mut self, text: &str, _position: Point, _style: &TextStyle, _: &Color) {
+            self.calls.push(Call::Text(text.to_string()));
+        }
+
+        fn measure_text(&mut self, text: &str, _style: &TextStyle, color: &Color) -> TextMetrics {
+            self.calls
+                .push(Call::Measure(text.to_string(), color.as_rgba_u32()));
+            TextMetrics::default()
+        }
+
+        fn save(&mut self) {
+            self.calls.push(Call::Save);
+        }
+
+        fn restore(&mut self) {
+            self.calls.push(Call::Restore);
+        }
+
+        fn clip(&mut self, _shape: &impl Shape) {
+            self.calls.push(Call::Clip);
+        }
+
+        fn transform(&mut self, transform: Affine) {
+            self.calls.push(Call::Transform);
+            self.transforms.push(transform);
+        }
+    }
+
+    fn vec2f(x: f32, y: f32) -> RenderOp {
+        RenderOp::PushVec2f(core::commands::Vec2f::new(x, y))
+    }
+
+    fn color(alpha: f32) -> RenderOp {
+        RenderOp::PushColor(core::commands::Color {
+            r: 1.,
+            g: 0.,
+            b: 0.,
+            a: alpha,
+        })
+    }
+
+    fn render(ops: Vec<RenderOp>) -> (Interpreter, RecordingBackend) {
+        let mut interpreter = Interpreter::new();
+        let mut backend = RecordingBackend::default();
+
+        interpreter.handle_render_commands(&mut backend, ops.into_iter().map(Ok));
+        (interpreter, backend)
+    }
+
+    #[test]
+    fn skips_commands_missing_arguments() {
+        let (_, backend) = render(vec![
+            RenderOp::PushClipRect,
+            RenderOp::DrawImage,
+            RenderOp::SetColorUniform,
+            RenderOp::PushFloat32(1.),
+            RenderOp::PushTransform,
+            RenderOp::PopTransform,
+        ]);
+
+        assert_eq!(backend.calls, vec![]);
+    }
+
+    #[test]
+    fn drops_leftover_arguments() {
+        let (interpreter, backend) = render(vec![
+            vec2f(0., 0.),
+            vec2f(10., 0.),
+            vec2f(10., 10.),
+            RenderOp::DrawLines,
+            RenderOp::DrawQuads,
+        ]);
+
+        assert_eq!(backend.count(&Call::Stroke), 1);
+        assert_eq!(backend.count(&Call::Fill), 0);
+        assert!(interpreter.arguments().is_empty());
+    }
+
+    #[test]
+    fn skips_undecodable_commands() {
+        let mut interpreter = Interpreter::new();
+        let mut backend = RecordingBackend::default();
+        let ops = vec![
+            Ok(RenderOp::PushString("label".into())),
+            Err(DecodeError::UnsupportedCommand { index: 1 }),
+            Ok(vec2f(0., 0.)),
+            Err(DecodeError::InvalidPayload {
+                index: 3,
+                command: "PushVec2f",
+            }),
+            Ok(RenderOp::DrawText),
+        ];
+
+        interpreter.handle_render_commands(&mut backend, ops);
+
+        assert_eq!(backend.count(&Call::Text("label".into())), 1);
+    }
+
+    #[test]
+    fn measures_text_in_the_uniform_color() {
+        let mut interpreter = Interpreter::new();
+        let mut backend = RecordingBackend::default();
+        let ops = vec![
+            color(1.),
+            RenderOp::SetColorUniform,
+            RenderOp::PushString("label".into()),
+            vec2f(0., 0.),
+            RenderOp::DrawText,
+        ];
+
+        let metrics = interpreter.handle_render_state(&mut backend, ops.into_iter().map(Ok));
+
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(
+            backend.calls,
+            vec![Call::Measure("label".into(), 0xFF0000FF)]
+        );
+    }
+
+    #[test]
+    fn keeps_save_and_restore_balanced() {
+        let (interpreter, backend) = render(vec![
+            RenderOp::PopClip,
+            RenderOp::PopTransform,
+            vec2f(0., 0.),
+            vec2f(10., 10.),
+            RenderOp::PushClipRect,
+            vec2f(0., 0.),
+            vec2f(5., 5.),
+            RenderOp::PushClipRect,
+            RenderOp::PopClip,
+        ]);
+
+        assert_eq!(backend.count(&Call::Clip), 2);
+        assert_eq!(backend.count(&Call::Save), backend.count(&Call::Restore));
+        assert_eq!(interpreter.clip_depth, 0);
+    }
+
+    #[test]
+    fn batches_only_opaque_commands() {
+        // Two crossing lines drawn by separate commands.
+        let scene = |alpha| {
+            vec![
+                color(alpha),
+                RenderOp::SetColorUniform,
+                vec2f(0., 0.),
+                vec2f(10., 10.),
+                RenderOp::DrawLines,
+                color(alpha),
+                RenderOp::SetColorUniform,
+                vec2f(0., 10.),
+                vec2f(10., 0.),
+                RenderOp::DrawLines,
+            ]
+        };
+
+        assert_eq!(render(scene(1.)).1.count(&Call::Stroke), 1);
+        assert_eq!(render(scene(0.5)).1.count(&Call::Stroke), 2);
+    }
+
+    #[test]
+    fn draws_point_shapes() {
+        let (_, backend) = render(vec![
+            vec2f(10., 10.),
+            RenderOp::PushFloat32(6.),
+            RenderOp::DrawPoints,
+            vec2f(20., 20.),
+            RenderOp::PushInt32(8),
+            RenderOp::PushInt32(1),
+            RenderOp::DrawPoints,
+            vec2f(30., 30.),
+            vec2f(40., 40.),
+            RenderOp::PushFloat32(2.),
+            RenderOp::PushInt32(1),
+            RenderOp::DrawPoints,
+            vec2f(50., 50.),
+            RenderOp::DrawPoints,
+        ]);
+
+        assert_eq!(
+            backend.circles,
+            vec![
+                Circle::new((10., 10.), 3.),
+                Circle::new((50., 50.), DEFAULT_POINT_SIZE / 2.),
+            ]
+        );
+        assert_eq!(
+            backend.quads,
+            vec![
+                Rect::from_center_size((20., 20.), (8., 8.)),
+                Rect::from_center_size((30., 30.), (2., 2.)),
+                Rect::from_center_size((40., 40.), (2., 2.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn draws_through_the_registered_cameras() {
+        let mut interpreter = Interpreter::new();
+        let mut backend = RecordingBackend::default();
+        let position = core::commands::Vec2f::new(10., 20.);
+
+        interpreter.handle_exec_commands(
+            vec![
+                ExecOp::PushInt32(3),
+                ExecOp::PushVec2f(position),
+                ExecOp::PushFloat32(2.),
+                ExecOp::PushFloat32(std::f32::consts::FRAC_PI_2),
+                ExecOp::UpdateCameraTransform,
+                // Cameras without a positive zoom are rejected.
+                ExecOp::PushInt32(4),
+                ExecOp::PushVec2f(position),
+                ExecOp::PushFloat32(0.),
+                ExecOp::UpdateCameraTransform,
+                ExecOp::PushInt32(5),
+                ExecOp::PushVec2f(position),
+                ExecOp::UpdateCameraPosition,
+            ]
+            .into_iter()
+            .map(Ok),
+        );
+
+        let ops = [3, 4, 5, 6].iter().flat_map(|camera_id| {
+            vec![
+                RenderOp::PushInt32(*camera_id),
+                RenderOp::SetCamera,
+                vec2f(0., 0.),
+                RenderOp::DrawPoints,
+            ]
+        });
+        interpreter.handle_render_commands(&mut backend, ops.map(Ok));
+
+        let rotation = std::f32::consts::FRAC_PI_2 as f64;
+        assert_eq!(
+            backend.transforms,
+            vec![
+                Affine::translate((10., 20.)) * Affine::rotate(rotation) * Affine::scale(2.),
+                Affine::IDENTITY,
+                Affine::translate((10., 20.)),
+                Affine::IDENTITY,
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_paths_from_verbs() {
+        let point = |index: usize| Point::new(index as f64, 1.);
+        let path = |verbs: &[i32], points: usize, command: RenderOp| {
+            let verbs = verbs.iter().map(|verb| RenderOp::PushInt32(*verb));
+            let points = (0..points).map(|index| vec2f(index as f32, 1.));
+            verbs.chain(points).chain(Some(command)).collect::<Vec<_>>()
+        };
+
+        let (_, backend) = render(
+            vec![
+                path(&[0, 1, 2, 3, 4], 7, RenderOp::StrokePath),
+                // Paths have to start with a move, use known verbs and have all their points.
+                path(&[1, 1], 2, RenderOp::FillPath),
+                path(&[0, 5], 2, RenderOp::FillPath),
+                path(&[0, 3], 3, RenderOp::FillPath),
+            ]
+            .concat(),
+        );
+
+        assert_eq!(backend.count(&Call::Fill), 0);
+        assert_eq!(
+            backend.paths,
+            vec![vec![
+                PathEl::MoveTo(point(0)),
+                PathEl::LineTo(point(1)),
+                PathEl::QuadTo(point(2), point(3)),
+                PathEl::CurveTo(point(4), point(5), point(6)),
+                PathEl::ClosePath,
+            ]]
+        );
+    }
+
+    #[test]
+    fn draws_arcs_ellipses_and_rounded_rects() {
+        let (_, backend) = render(vec![
+            vec2f(0., 0.),
+            vec2f(10., 10.),
+            vec2f(20., 0.),
+            vec2f(10., 5.),
+            RenderOp::PushFloat32(0.),
+            RenderOp::PushFloat32(std::f32::consts::PI),
+            RenderOp::PushFloat32(0.),
+            RenderOp::PushFloat32(1.),
+            RenderOp::DrawArcs,
+            vec2f(50., 50.),
+            vec2f(20., 10.),
+            RenderOp::DrawEllipses,
+            RenderOp::PushInt32(1),
+            vec2f(50., 50.),
+            vec2f(20., 10.),
+            RenderOp::DrawEllipses,
+            vec2f(0., 0.),
+            vec2f(30., 20.),
+            RenderOp::PushFloat32(5.),
+            RenderOp::DrawRoundedRects,
+        ]);
+
+        let ellipse = Rect::new(30., 40., 70., 60.);
+        let rounded_rect = Rect::new(0., 0., 30., 20.);
+        let is_near = |a: Rect, b: Rect| {
+            let deltas = [a.x0 - b.x0, a.y0 - b.y0, a.x1 - b.x1, a.y1 - b.y1];
+            deltas.iter().all(|delta| delta.abs() < 1e-9)
+        };
+
+        assert_eq!(backend.count(&Call::Stroke), 3);
+        assert_eq!(backend.count(&Call::Fill), 2);
+        assert_eq!(backend.bounds.len(), 5);
+        assert!(is_near(backend.bounds[2], ellipse));
+        assert!(is_near(backend.bounds[3], ellipse));
+        assert!(is_near(backend.bounds[4], rounded_rect));
+    }
+
+    #[test]
+    fn parses_stroke_styles() {
+        let style = |floats: &[f32], ints: &[i32]| {
+            let floats = floats.iter().map(|value| RenderOp::PushFloat32(*value));
+            let ints = ints.iter().map(|value| RenderOp::PushInt32(*value));
+            floats
+                .chain(ints)
+                .chain(Some(RenderOp::SetStrokeStyle))
+                .collect::<Vec<_>>()
+        };
+        let line = || {
+            vec![
+                RenderOp::PushInt32(0),
+                RenderOp::PushInt32(1),
+                vec2f(0., 0.),
+                vec2f(10., 0.),
+                RenderOp::StrokePath,
+            ]
+        };
+
+        let (_, backend) = render(
+            vec![
+                style(&[2., 1., 4., 2.], &[1, 2]),
+                line(),
+                style(&[3.], &[2]),
+                line(),
+                // Invalid widths and dash arrays keep the default stroke.
+                style(&[-1.], &[]),
+                line(),
+                style(&[1., 0., 4., -2.], &[]),
+                line(),
+                style(&[1., 0., 0., 0.], &[]),
+                line(),
+            ]
+            .concat(),
+        );
+
+        assert_eq!(
+            backend.strokes,
+            vec![
+                Stroke {
+                    width: 2.,
+                    dash: vec![4., 2.],
+                    dash_offset: 1.,
+                    cap: LineCap::Round,
+                    join: LineJoin::Bevel,
+                },
+                Stroke {
+                    width: 3.,
+                    cap: LineCap::Square,
+                    ..Stroke::default()
+                },
+                Stroke::default(),
+                Stroke::default(),
+                Stroke::default(),
+            ]
+        );
+    }
+}
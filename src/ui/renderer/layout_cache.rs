@@ -0,0 +1,307 @@
+//! A bounded cache of shaped text layouts.
+
+use std::collections::hash_map::{self, HashMap};
+
+use druid::Color;
+
+use super::text::{FontFamily, HorizontalAlignment, TextMetrics, TextStyle};
+
+/// Enough for the labels of a large scheme.
+pub const DEFAULT_CAPACITY: usize = 4096;
+/// Layouts kept per string, enough for a string drawn in a few colors or fading.
+const COLORS_PER_LAYOUT: usize = 4;
+
+/// The layout-relevant part of a text command: the vertical alignment only moves the layout,
+/// so strings differing in it share their layouts. The horizontal alignment also aligns the
+/// lines inside the layout.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutKey {
+    text: String,
+    family: FontFamily,
+    size_bits: u64,
+    weight: u16,
+    italic: bool,
+    max_width_bits: Option<u64>,
+    alignment: HorizontalAlignment,
+}
+
+impl LayoutKey {
+    pub fn new(text: &str, style: &TextStyle) -> Self {
+        LayoutKey {
+            text: text.to_string(),
+            family: style.family.clone(),
+            size_bits: style.size.to_bits(),
+            weight: style.weight,
+            italic: style.italic,
+            max_width_bits: style.max_width.map(f64::to_bits),
+            alignment: style.horizontal_alignment,
+        }
+    }
+}
+
+struct CachedLayouts<L> {
+    /// The metrics don't depend on the color, they are shared by all layouts. Backends may
+    /// complete them later, e.g. with the metrics only the measurement pass needs.
+    metrics: TextMetrics,
+    /// Layouts bake their color in, one per color the string was recently drawn with, most
+    /// recently used first.
+    layouts: Vec<(u32, L)>,
+    last_used: u64,
+}
+
+/// Least recently used cache of text layouts, shared by the measurement and the render pass
+/// and kept across frames.
+///
+/// The capacity counts the layouts of every color. When the cache grows over it the least
+/// recently used quarter is evicted at once, so the eviction cost is amortized over many
+/// insertions.
+pub struct LayoutCache<L> {
+    entries: HashMap<LayoutKey, CachedLayouts<L>>,
+    /// The number of layouts of all entries.
+    len: usize,
+    capacity: usize,
+    clock: u64,
+}
+
+impl<L> LayoutCache<L> {
+    pub fn new(capacity: usize) -> Self {
+        LayoutCache {
+            entries: HashMap::new(),
+            len: 0,
+            capacity: capacity.max(1),
+            clock: 0,
+        }
+    }
+
+    /// Returns the metrics of the string if any of its layouts is cached.
+    pub fn metrics(&mut self, key: &LayoutKey) -> Option<&TextMetrics> {
+        let clock = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = clock;
+
+        Some(&entry.metrics)
+    }
+
+    /// Returns the most recently used layout of the string with its metrics for completing.
+    pub fn get_mut(&mut self, key: &LayoutKey) -> Option<(&L, &mut TextMetrics)> {
+        let clock = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = clock;
+
+        let (_, layout) = entry.layouts.first()?;
+        Some((layout, &mut entry.metrics))
+    }
+
+    /// Returns the layout of the string in the color, building it with `build` on a miss.
+    pub fn get_or_build(
+        &mut self,
+        key: LayoutKey,
+        color: &Color,
+        build: impl FnOnce() -> Option<(L, TextMetrics)>,
+    ) -> Option<(&L, &TextMetrics)> {
+        let clock = self.tick();
+        let rgba = color.as_rgba_u32();
+
+        let cached = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.last_used = clock;
+                entry.layouts.iter().any(|(color, _)| *color == rgba)
+            }
+            None => false,
+        };
+
+        if !cached {
+            self.evict();
+        }
+
+        let entry = match self.entries.entry(key) {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Vacant(entry) => {
+                let (layout, metrics) = build()?;
+                let entry = entry.insert(CachedLayouts {
+                    metrics,
+                    layouts: vec![(rgba, layout)],
+                    last_used: clock,
+                });
+
+                self.len += 1;
+                return Some((&entry.layouts[0].1, &entry.metrics));
+            }
+        };
+
+        match entry.layouts.iter().position(|(color, _)| *color == rgba) {
+            Some(index) => entry.layouts[..=index].rotate_right(1),
+            None => {
+                let (layout, _) = build()?;
+                entry.layouts.insert(0, (rgba, layout));
+
+                if entry.layouts.len() > COLORS_PER_LAYOUT {
+                    entry.layouts.pop();
+                }
+                else {
+                    self.len += 1;
+                }
+            }
+        }
+
+        Some((&entry.layouts[0].1, &entry.metrics))
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Makes room for a new layout.
+    fn evict(&mut self) {
+        if self.len < self.capacity {
+            return;
+        }
+
+        let mut entries: Vec<(u64, usize)> = self
+            .entries
+            .values()
+            .map(|entry| (entry.last_used, entry.layouts.len()))
+            .collect();
+        entries.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+
+        // Keeps the most recently used entries as long as their layouts fit.
+        let keep = self.capacity - self.capacity / 4 - 1;
+        let mut kept = 0;
+        let mut threshold = 0;
+
+        for (last_used, count) in entries {
+            kept += count;
+
+            if kept > keep {
+                threshold = last_used;
+                break;
+            }
+        }
+
+        self.entries.retain(|_, entry| entry.last_used > threshold);
+        self.len = self.entries.values().map(|entry| entry.layouts.len()).sum();
+    }
+}
+
+impl<L> Default for LayoutCache<L> {
+    fn default() -> Self {
+        LayoutCache::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::renderer::text::VerticalAlignment;
+    use druid::Size;
+
+    fn metrics() -> TextMetrics {
+        TextMetrics {
+            size: Size::new(10., 12.),
+            baseline: 9.,
+            ..TextMetrics::default()
+        }
+    }
+
+    #[test]
+    fn shares_layouts_between_measure_and_draw() {
+        let mut cache = LayoutCache::new(8);
+        let key = LayoutKey::new("label", &TextStyle::default());
+        let mut builds = 0;
+
+        assert_eq!(cache.metrics(&key), None);
+
+        for _ in 0..3 {
+            cache.get_or_build(key.clone(), &Color::BLACK, || {
+                builds += 1;
+                Some(((), metrics()))
+            });
+        }
+
+        assert_eq!(builds, 1);
+        assert_eq!(cache.metrics(&key), Some(&metrics()));
+    }
+
+    #[test]
+    fn separates_horizontal_alignments() {
+        use HorizontalAlignment::{Center, Left};
+        use VerticalAlignment::{Baseline, Top};
+
+        let key = |horizontal_alignment, vertical_alignment| {
+            let style = TextStyle {
+                horizontal_alignment,
+                vertical_alignment,
+                ..TextStyle::default()
+            };
+            LayoutKey::new("two\nlines", &style)
+        };
+
+        assert_ne!(key(Left, Top), key(Center, Top));
+        assert_eq!(key(Left, Top), key(Left, Baseline));
+    }
+
+    #[test]
+    fn completes_the_shared_metrics() {
+        let mut cache = LayoutCache::new(8);
+        let key = LayoutKey::new("label", &TextStyle::default());
+
+        cache.get_or_build(key.clone(), &Color::BLACK, || Some((0, metrics())));
+        cache.get_or_build(key.clone(), &Color::WHITE, || Some((1, metrics())));
+
+        let (layout, metrics) = cache.get_mut(&key).unwrap();
+        assert_eq!(*layout, 1);
+        metrics.ascent = 9.;
+
+        assert_eq!(cache.metrics(&key).map(|metrics| metrics.ascent), Some(9.));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LayoutCache::new(4);
+        let key = |index: usize| LayoutKey::new(&index.to_string(), &TextStyle::default());
+
+        for index in 0..4 {
+            cache.get_or_build(key(index), &Color::BLACK, || Some(((), metrics())));
+        }
+
+        cache.metrics(&key(0));
+        cache.get_or_build(key(4), &Color::BLACK, || Some(((), metrics())));
+
+        assert!(cache.entries.len() <= 4);
+        assert!(cache.metrics(&key(0)).is_some());
+        assert!(cache.metrics(&key(4)).is_some());
+        assert!(cache.metrics(&key(1)).is_none());
+    }
+
+    #[test]
+    fn bounds_the_layouts_of_a_string() {
+        let mut cache = LayoutCache::new(64);
+        let key = LayoutKey::new("label", &TextStyle::default());
+
+        for alpha in 0..16 {
+            let color = Color::rgba8(0, 0, 0, alpha);
+            cache.get_or_build(key.clone(), &color, || Some(((), metrics())));
+        }
+
+        assert_eq!(cache.entries[&key].layouts.len(), COLORS_PER_LAYOUT);
+        assert_eq!(cache.len, COLORS_PER_LAYOUT);
+    }
+
+    #[test]
+    fn counts_every_layout_toward_the_capacity() {
+        let mut cache = LayoutCache::new(4);
+        let key = |index: usize| LayoutKey::new(&index.to_string(), &TextStyle::default());
+
+        for alpha in 0..4 {
+            let color = Color::rgba8(0, 0, 0, alpha);
+            cache.get_or_build(key(0), &color, || Some(((), metrics())));
+        }
+
+        cache.get_or_build(key(1), &Color::BLACK, || Some(((), metrics())));
+
+        assert!(cache.len <= 4);
+        assert!(cache.metrics(&key(0)).is_none());
+        assert!(cache.metrics(&key(1)).is_some());
+    }
+}
@@ -0,0 +1,311 @@
+mod backend;
+mod batch;
+mod bench;
+mod camera;
+mod canvas;
+mod clock;
+mod commands;
+mod diagnostics;
+mod grid;
+mod headless;
+mod images;
+mod input;
+mod interpreter;
+mod layout_cache;
+mod recorder;
+mod scheduler;
+mod stroke;
+mod svg;
+mod text;
+mod viewport;
+
+use druid::piet::{PietImage, PietTextLayout};
+use druid::widget::prelude::*;
+use druid::{Data, Selector, TimerToken, WindowHandle};
+use std::marker::PhantomData;
+use std::mem;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use tech_paws_core as core;
+
+pub use backend::{PietBackend, RenderBackend, TeeBackend};
+pub use bench::{line_benchmark, BenchmarkResult, SceneBenchmark};
+pub use camera::{Camera, Cameras};
+pub use canvas::{Background, CanvasStyle};
+pub use clock::{SimulationClock, DEFAULT_TIMESTEP};
+pub use grid::{Grid, GridColors, GridStyle};
+pub use headless::SnapshotOptions;
+pub use images::{Image, ImageCache};
+pub use interpreter::Interpreter;
+pub use layout_cache::LayoutCache;
+pub use recorder::{Recorder, Replay, ReplayError};
+pub use scheduler::{FrameSchedule, FrameScheduler};
+pub use stroke::Stroke;
+pub use svg::SvgBackend;
+pub use text::{FontFamily, HorizontalAlignment, TextStyle, VerticalAlignment};
+pub use viewport::Viewport;
+
+/// Exports the next frame of the canvas as an SVG file to the given path.
+pub const EXPORT_SVG: Selector<PathBuf> = Selector::new("paws.tech.renderer.export-svg");
+
+pub struct Renderer<T> {
+    scheduler: FrameScheduler,
+    clock: SimulationClock,
+    data: PhantomData<T>,
+    interpreter: Interpreter,
+    layouts: LayoutCache<PietTextLayout>,
+    images: ImageCache<PietImage>,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    svg_export_path: Option<PathBuf>,
+    /// The viewport last sent to the core.
+    viewport: Option<Viewport>,
+    canvas: CanvasStyle,
+}
+
+impl<T: Data> Renderer<T> {
+    pub fn new() -> Self {
+        Renderer {
+            scheduler: FrameScheduler::new(FrameSchedule::default()),
+            clock: SimulationClock::new(DEFAULT_TIMESTEP),
+            data: PhantomData,
+            interpreter: Interpreter::new(),
+            layouts: LayoutCache::default(),
+            images: ImageCache::new(),
+            recorder: None,
+            replay: None,
+            svg_export_path: None,
+            viewport: None,
+            canvas: CanvasStyle::default(),
+        }
+    }
+
+    /// Builder-style method to record every frame into the recorder.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Builder-style method to play a recorded stream instead of stepping the core.
+    pub fn with_replay(mut self, replay: Replay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Builder-style method to set the FPS cap and the redraw policy.
+    pub fn with_schedule(mut self, schedule: FrameSchedule) -> Self {
+        self.scheduler = FrameScheduler::new(schedule);
+        self
+    }
+
+    /// Builder-style method to set the duration of a simulation step.
+    pub fn with_timestep(mut self, timestep: Duration) -> Self {
+        self.clock = SimulationClock::new(timestep);
+        self
+    }
+
+    /// Builder-style method to log malformed commands of the core.
+    pub fn with_validation(mut self, validate: bool) -> Self {
+        self.interpreter.set_validation(validate);
+        self
+    }
+
+    /// Sends the viewport to the core if it changed since the last time.
+    fn set_viewport(&mut self, viewport: Viewport) {
+        if self.viewport == Some(viewport) {
+            return;
+        }
+
+        self.push_to_core(|| viewport.push_to_core());
+        self.interpreter.set_viewport(viewport);
+        self.viewport = Some(viewport);
+    }
+
+    /// Paints a whole frame of the core into the framed canvas, running `steps` simulation
+    /// steps first.
+    ///
+    /// Returns `true` if the core asks for another frame or images are still loading.
+    fn paint_frame<B: RenderBackend>(&mut self, backend: &mut B, size: Size, steps: usize) -> bool {
+        self.interpreter.set_canvas_style(self.canvas.clone());
+
+        self.canvas.clip(backend, size);
+        let animating = self.run_frame(backend, steps);
+        self.canvas.paint_border(backend, size);
+
+        animating || self.interpreter.is_loading_images()
+    }
+
+    /// Runs a frame and returns `true` if another one is needed to finish an animation.
+    ///
+    /// The core tells it with `needs_redraw`, which stays `true` while any of its state is
+    /// still changing on its own.
+    fn run_frame<B: RenderBackend>(&mut self, backend: &mut B, steps: usize) -> bool {
+        match &mut self.replay {
+            Some(replay) => {
+                if let Some(frame) = replay.next_frame() {
+                    self.interpreter.replay_frame(backend, frame);
+                }
+
+                !replay.is_finished()
+            }
+            None => {
+                self.interpreter
+                    .frame(backend, steps, self.recorder.as_mut());
+                core::needs_redraw()
+            }
+        }
+    }
+
+    /// Runs `push` to send something to the core, unless a replay plays instead of the core.
+    ///
+    /// The core never steps during a replay, so nothing would consume what is pushed.
+    fn push_to_core(&self, push: impl FnOnce()) {
+        if self.replay.is_none() {
+            push();
+        }
+    }
+
+    /// Schedules a repaint after something changed.
+    fn wake(&mut self, ctx: &mut EventCtx) {
+        if self.schedule_paint(|deadline| ctx.request_timer(deadline)) {
+            ctx.request_paint();
+        }
+    }
+
+    /// Marks the canvas as changed, returns `true` if it should be painted right away.
+    ///
+    /// Every repaint goes through here so the timer keeps ticking while the core animates.
+    fn schedule_paint(&mut self, request_timer: impl FnOnce(Duration) -> TimerToken) -> bool {
+        self.scheduler.wake(request_timer)
+    }
+}
+
+impl<T: Data> Widget<T> for Renderer<T> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut T, _env: &Env) {
+        match event {
+            Event::MouseDown(mouse) => {
+                ctx.request_focus();
+                self.push_to_core(|| {
+                    core::push_on_touch_start_request_command(input::touch(mouse))
+                });
+                self.wake(ctx);
+            }
+            Event::MouseUp(mouse) => {
+                self.push_to_core(|| core::push_on_touch_end_request_command(input::touch(mouse)));
+                self.wake(ctx);
+            }
+            Event::MouseMove(mouse) => {
+                self.push_to_core(|| core::push_on_touch_move_request_command(input::touch(mouse)));
+                self.wake(ctx);
+            }
+            Event::KeyDown(key_event) => {
+                self.push_to_core(|| input::push_key_down(key_event));
+                self.wake(ctx);
+                ctx.set_handled();
+            }
+            Event::KeyUp(key_event) => {
+                self.push_to_core(|| input::push_key_up(key_event));
+                self.wake(ctx);
+                ctx.set_handled();
+            }
+            Event::Wheel(mouse) => {
+                self.push_to_core(|| input::push_wheel(mouse));
+                self.wake(ctx);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(EXPORT_SVG) => {
+                self.svg_export_path = Some(cmd.get_unchecked(EXPORT_SVG).clone());
+                self.wake(ctx);
+            }
+            Event::WindowConnected => self.wake(ctx),
+            Event::Timer(id) => {
+                if self
+                    .scheduler
+                    .tick(*id, |deadline| ctx.request_timer(deadline))
+                {
+                    ctx.request_paint();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &T, _env: &Env) {
+        match event {
+            LifeCycle::WidgetAdded => ctx.register_for_focus(),
+            LifeCycle::Size(size) => {
+                let scale = window_scale(ctx.window());
+                self.set_viewport(Viewport::new(*size, ctx.window_origin(), scale));
+
+                if self.schedule_paint(|deadline| ctx.request_timer(deadline)) {
+                    ctx.request_paint();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, _data: &T, _env: &Env) {
+        if self.schedule_paint(|deadline| ctx.request_timer(deadline)) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _layout_ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &T,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _: &T, env: &Env) {
+        let size = ctx.size();
+        self.canvas = CanvasStyle::from_env(env);
+
+        // The canvas can move or change its scale without being resized.
+        let scale = window_scale(ctx.window());
+        self.set_viewport(Viewport::new(size, ctx.window_origin(), scale));
+
+        // Only frames following an animating one catch up with the time in between. Otherwise
+        // the core was idle, e.g. the OS asks to repaint a window that was uncovered.
+        if !self.scheduler.is_animating() {
+            self.clock.resume();
+        }
+
+        let steps = self.clock.advance(Instant::now());
+
+        // The caches are moved out for the frame since the backend borrows them next to `self`.
+        let mut layouts = mem::take(&mut self.layouts);
+        let mut images = mem::take(&mut self.images);
+        let mut backend = PietBackend::new(ctx.render_ctx, &mut layouts, &mut images);
+
+        let animating = match self.svg_export_path.take() {
+            Some(path) => {
+                let mut svg = SvgBackend::new(size);
+                let animating =
+                    self.paint_frame(&mut TeeBackend::new(&mut backend, &mut svg), size, steps);
+
+                match svg.save_to_file(&path) {
+                    Ok(()) => log::info!("Exported the scheme to {}", path.display()),
+                    Err(err) => log::error!("Failed to export {}: {}", path.display(), err),
+                }
+
+                animating
+            }
+            None => self.paint_frame(&mut backend, size, steps),
+        };
+
+        self.layouts = layouts;
+        self.images = images;
+        self.scheduler.frame_painted(animating);
+    }
+}
+
+/// The number of device pixels per device independent pixel of the window.
+fn window_scale(window: &WindowHandle) -> f64 {
+    window.get_scale().map(|scale| scale.x()).unwrap_or(1.)
+}
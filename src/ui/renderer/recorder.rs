@@ -0,0 +1,555 @@
+//! Recording and replaying of the command streams of the core.
+//!
+//! Streams are stored as a versioned, line based text file:
+//!
+//! ```text
+//! tech-paws-render-stream 3
+//! frame
+//! exec push_int32 0
+//! exec push_vec2f 10 20
+//! exec update_camera_position
+//! state push_string "Hello"
+//! state draw_text
+//! render push_color 0 0 0 1
+//! render set_color_uniform
+//! render !unsupported_command
+//! render !invalid_payload PushVec2f
+//! ```
+//!
+//! `exec`, `state` and `render` lines hold the execution commands, the measurement pass
+//! and the render pass of the current frame respectively. Commands that failed to decode are
+//! recorded as `!` lines, so a replay reports them like the live frame did. Payloads are
+//! validated like the ones read from the core, so a stream can't feed the interpreter
+//! anything the core can't.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use tech_paws_core as core;
+
+use super::commands::{self, DecodeError, ExecOp, RenderOp};
+
+const MAGIC: &str = "tech-paws-render-stream";
+/// Bumped whenever commands are added or their syntax changes.
+const VERSION: u32 = 3;
+/// The commands with a payload, the ones a payload error can be recorded for.
+const PAYLOAD_COMMANDS: [&str; 6] = [
+    "PushVec2f",
+    "PushInt32",
+    "PushFloat32",
+    "PushColor",
+    "PushString",
+    "PushBytes",
+];
+
+/// The commands the core produced during one frame, including the ones that failed to decode.
+#[derive(Clone, Default)]
+pub struct Frame {
+    pub exec: Vec<Result<ExecOp, DecodeError>>,
+    pub state: Vec<Result<RenderOp, DecodeError>>,
+    pub render: Vec<Result<RenderOp, DecodeError>>,
+}
+
+/// Writes every recorded frame to a stream file.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{} {}", MAGIC, VERSION)?;
+
+        Ok(Recorder { writer })
+    }
+
+    /// Appends the frame to the file.
+    ///
+    /// The writer is flushed after every frame so a crash keeps everything recorded so far.
+    pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        write_frame(&mut self.writer, frame)?;
+        self.writer.flush()
+    }
+}
+
+/// Frames loaded from a stream file, played back one per paint.
+pub struct Replay {
+    frames: Vec<Frame>,
+    position: usize,
+}
+
+impl Replay {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let file = File::open(path).map_err(ReplayError::Io)?;
+        Replay::read(BufReader::new(file))
+    }
+
+    pub fn read(reader: impl BufRead) -> Result<Self, ReplayError> {
+        let mut lines = reader.lines().enumerate();
+
+        match lines.next() {
+            Some((_, line)) => {
+                let line = line.map_err(ReplayError::Io)?;
+                let mut header = line.split(' ');
+
+                if header.next() != Some(MAGIC) {
+                    return Err(ReplayError::NotAStream);
+                }
+
+                let version = header.next().and_then(|version| version.parse().ok());
+
+                if version != Some(VERSION) {
+                    return Err(ReplayError::UnsupportedVersion(line));
+                }
+            }
+            None => return Err(ReplayError::NotAStream),
+        }
+
+        let mut frames = Vec::new();
+
+        for (index, line) in lines {
+            let line = line.map_err(ReplayError::Io)?;
+            let line_number = index + 1;
+            let parse_error = || {
+                ReplayError::Parse {
+                    line_number,
+                    line: line.clone(),
+                }
+            };
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == "frame" {
+                frames.push(Frame::default());
+                continue;
+            }
+
+            let frame = frames.last_mut().ok_or_else(parse_error)?;
+            let (section, op) = split_word(&line);
+
+            match section {
+                "exec" => {
+                    let index = frame.exec.len();
+                    let exec_op = match parse_error_op(index, op) {
+                        Some(err) => Some(Err(err)),
+                        None => {
+                            parse_exec_op(op)
+                                .and_then(|op| commands::validate_exec_op(index, op).ok())
+                                .map(Ok)
+                        }
+                    };
+                    frame.exec.push(exec_op.ok_or_else(parse_error)?);
+                }
+                "state" | "render" => {
+                    let ops = if section == "state" {
+                        &mut frame.state
+                    }
+                    else {
+                        &mut frame.render
+                    };
+                    let index = ops.len();
+                    let render_op = match parse_error_op(index, op) {
+                        Some(err) => Some(Err(err)),
+                        None => {
+                            parse_render_op(op)
+                                .and_then(|op| commands::validate_render_op(index, op).ok())
+                                .map(Ok)
+                        }
+                    };
+                    ops.push(render_op.ok_or_else(parse_error)?);
+                }
+                _ => return Err(parse_error()),
+            }
+        }
+
+        Ok(Replay {
+            frames,
+            position: 0,
+        })
+    }
+
+    /// Returns the next frame, holding on the last one when the stream is over.
+    pub fn next_frame(&mut self) -> Option<&Frame> {
+        let index = self.position.min(self.frames.len().checked_sub(1)?);
+        self.position = index + 1;
+        self.frames.get(index)
+    }
+
+    /// Returns `true` once the last frame has been played.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    NotAStream,
+    UnsupportedVersion(String),
+    Parse { line_number: usize, line: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::NotAStream => write!(f, "not a render stream file"),
+            ReplayError::UnsupportedVersion(header) => {
+                write!(f, "unsupported render stream version: {}", header)
+            }
+            ReplayError::Parse { line_number, line } => {
+                write!(f, "malformed command at line {}: {}", line_number, line)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+fn write_frame(writer: &mut impl Write, frame: &Frame) -> io::Result<()> {
+    writeln!(writer, "frame")?;
+
+    for op in &frame.exec {
+        let line = op.as_ref().map_or_else(format_error_op, format_exec_op);
+        writeln!(writer, "exec {}", line)?;
+    }
+
+    for op in &frame.state {
+        let line = op.as_ref().map_or_else(format_error_op, format_render_op);
+        writeln!(writer, "state {}", line)?;
+    }
+
+    for op in &frame.render {
+        let line = op.as_ref().map_or_else(format_error_op, format_render_op);
+        writeln!(writer, "render {}", line)?;
+    }
+
+    Ok(())
+}
+
+/// Formats a command that failed to decode. Errors of whole buffers never end up in a frame.
+fn format_error_op(err: &DecodeError) -> String {
+    match err {
+        DecodeError::InvalidPayload { command, .. } => format!("!invalid_payload {}", command),
+        _ => "!unsupported_command".into(),
+    }
+}
+
+fn format_exec_op(op: &ExecOp) -> String {
+    match op {
+        ExecOp::PushVec2f(vec) => format!("push_vec2f {} {}", vec.x, vec.y),
+        ExecOp::PushInt32(value) => format!("push_int32 {}", value),
+        ExecOp::PushFloat32(value) => format!("push_float32 {}", value),
+        ExecOp::UpdateCameraPosition => "update_camera_position".into(),
+        ExecOp::UpdateCameraTransform => "update_camera_transform".into(),
+    }
+}
+
+fn format_render_op(op: &RenderOp) -> String {
+    match op {
+        RenderOp::PushColor(color) => {
+            format!("push_color {} {} {} {}", color.r, color.g, color.b, color.a)
+        }
+        RenderOp::PushVec2f(vec) => format!("push_vec2f {} {}", vec.x, vec.y),
+        RenderOp::PushInt32(value) => format!("push_int32 {}", value),
+        RenderOp::PushFloat32(value) => format!("push_float32 {}", value),
+        RenderOp::PushString(str) => format!("push_string {:?}", str),
+        RenderOp::PushBytes(bytes) => format!("push_bytes {}", hex(bytes)),
+        RenderOp::DrawText => "draw_text".into(),
+        RenderOp::DrawLines => "draw_lines".into(),
+        RenderOp::DrawQuads => "draw_quads".into(),
+        RenderOp::DrawPoints => "draw_points".into(),
+        RenderOp::StrokePath => "stroke_path".into(),
+        RenderOp::FillPath => "fill_path".into(),
+        RenderOp::DrawArcs => "draw_arcs".into(),
+        RenderOp::DrawEllipses => "draw_ellipses".into(),
+        RenderOp::DrawRoundedRects => "draw_rounded_rects".into(),
+        RenderOp::DrawImage => "draw_image".into(),
+        RenderOp::RegisterImage => "register_image".into(),
+        RenderOp::ReleaseImage => "release_image".into(),
+        RenderOp::PushClipRect => "push_clip_rect".into(),
+        RenderOp::PopClip => "pop_clip".into(),
+        RenderOp::PushTransform => "push_transform".into(),
+        RenderOp::PopTransform => "pop_transform".into(),
+        RenderOp::SetCamera => "set_camera".into(),
+        RenderOp::SetColorUniform => "set_color_uniform".into(),
+        RenderOp::SetTextStyle => "set_text_style".into(),
+        RenderOp::SetStrokeStyle => "set_stroke_style".into(),
+        RenderOp::SetCanvasBackground => "set_canvas_background".into(),
+        RenderOp::SetGrid => "set_grid".into(),
+    }
+}
+
+fn parse_exec_op(line: &str) -> Option<ExecOp> {
+    let (name, args) = split_word(line);
+
+    match name {
+        "push_vec2f" => parse_vec2f(args).map(ExecOp::PushVec2f),
+        "push_int32" => parse_number(args).map(ExecOp::PushInt32),
+        "push_float32" => parse_number(args).map(ExecOp::PushFloat32),
+        "update_camera_position" => Some(ExecOp::UpdateCameraPosition),
+        "update_camera_transform" => Some(ExecOp::UpdateCameraTransform),
+        _ => None,
+    }
+}
+
+fn parse_render_op(line: &str) -> Option<RenderOp> {
+    let (name, args) = split_word(line);
+
+    match name {
+        "push_color" => parse_color(args).map(RenderOp::PushColor),
+        "push_vec2f" => parse_vec2f(args).map(RenderOp::PushVec2f),
+        "push_int32" => parse_number(args).map(RenderOp::PushInt32),
+        "push_float32" => parse_number(args).map(RenderOp::PushFloat32),
+        "push_string" => unescape(args).map(RenderOp::PushString),
+        "push_bytes" => parse_hex(args).map(RenderOp::PushBytes),
+        "draw_text" => Some(RenderOp::DrawText),
+        "draw_lines" => Some(RenderOp::DrawLines),
+        "draw_quads" => Some(RenderOp::DrawQuads),
+        "draw_points" => Some(RenderOp::DrawPoints),
+        "stroke_path" => Some(RenderOp::StrokePath),
+        "fill_path" => Some(RenderOp::FillPath),
+        "draw_arcs" => Some(RenderOp::DrawArcs),
+        "draw_ellipses" => Some(RenderOp::DrawEllipses),
+        "draw_rounded_rects" => Some(RenderOp::DrawRoundedRects),
+        "draw_image" => Some(RenderOp::DrawImage),
+        "register_image" => Some(RenderOp::RegisterImage),
+        "release_image" => Some(RenderOp::ReleaseImage),
+        "push_clip_rect" => Some(RenderOp::PushClipRect),
+        "pop_clip" => Some(RenderOp::PopClip),
+        "push_transform" => Some(RenderOp::PushTransform),
+        "pop_transform" => Some(RenderOp::PopTransform),
+        "set_camera" => Some(RenderOp::SetCamera),
+        "set_color_uniform" => Some(RenderOp::SetColorUniform),
+        "set_text_style" => Some(RenderOp::SetTextStyle),
+        "set_stroke_style" => Some(RenderOp::SetStrokeStyle),
+        "set_canvas_background" => Some(RenderOp::SetCanvasBackground),
+        "set_grid" => Some(RenderOp::SetGrid),
+        _ => None,
+    }
+}
+
+/// Parses a command that failed to decode, `None` for any other line.
+fn parse_error_op(index: usize, line: &str) -> Option<DecodeError> {
+    let (name, args) = split_word(line);
+
+    match name {
+        "!unsupported_command" => Some(DecodeError::UnsupportedCommand { index }),
+        "!invalid_payload" => {
+            let command = PAYLOAD_COMMANDS
+                .iter()
+                .copied()
+                .find(|command| *command == args.trim())?;
+
+            Some(DecodeError::InvalidPayload { index, command })
+        }
+        _ => None,
+    }
+}
+
+fn split_word(line: &str) -> (&str, &str) {
+    match line.find(' ') {
+        Some(index) => (&line[..index], &line[index + 1..]),
+        None => (line, ""),
+    }
+}
+
+fn parse_number<N: FromStr>(str: &str) -> Option<N> {
+    str.trim().parse().ok()
+}
+
+fn parse_vec2f(args: &str) -> Option<core::commands::Vec2f> {
+    let mut args = args.split_whitespace();
+
+    Some(core::commands::Vec2f {
+        x: parse_number(args.next()?)?,
+        y: parse_number(args.next()?)?,
+    })
+}
+
+fn parse_color(args: &str) -> Option<core::commands::Color> {
+    let mut args = args.split_whitespace();
+
+    Some(core::commands::Color {
+        r: parse_number(args.next()?)?,
+        g: parse_number(args.next()?)?,
+        b: parse_number(args.next()?)?,
+        a: parse_number(args.next()?)?,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(args: &str) -> Option<Vec<u8>> {
+    let args = args.trim();
+
+    if args.len() % 2 != 0 || !args.is_ascii() {
+        return None;
+    }
+
+    (0..args.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&args[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// Parses a string literal written with the `Debug` formatting of `str`.
+fn unescape(literal: &str) -> Option<String> {
+    let literal = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            '0' => result.push('\0'),
+            '\\' => result.push('\\'),
+            '"' => result.push('"'),
+            '\'' => result.push('\''),
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+
+                let code: String = chars.by_ref().take_while(|ch| *ch != '}').collect();
+                let code = u32::from_str_radix(&code, 16).ok()?;
+                result.push(std::char::from_u32(code)?);
+            }
+            _ => return None,
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let color = core::commands::Color::rgb(0.25, 0.5, 1.);
+        let frame = Frame {
+            exec: vec![
+                Ok(ExecOp::PushInt32(1)),
+                Ok(ExecOp::PushVec2f(core::commands::Vec2f::new(10.5, -3.))),
+                Ok(ExecOp::UpdateCameraPosition),
+            ],
+            state: vec![
+                Ok(RenderOp::PushString("Node \"A\"\n\u{7f}".into())),
+                Ok(RenderOp::DrawText),
+            ],
+            render: vec![
+                Ok(RenderOp::PushColor(color)),
+                Ok(RenderOp::SetColorUniform),
+                Ok(RenderOp::DrawLines),
+            ],
+        };
+
+        let mut buffer = Vec::new();
+        writeln!(buffer, "{} {}", MAGIC, VERSION).unwrap();
+        write_frame(&mut buffer, &frame).unwrap();
+
+        let mut replay = Replay::read(buffer.as_slice()).unwrap();
+        let replayed = replay.next_frame().unwrap();
+
+        assert_eq!(replayed.exec.len(), 3);
+        assert!(
+            matches!(replayed.exec[1], Ok(ExecOp::PushVec2f(vec)) if vec.x == 10.5 && vec.y == -3.)
+        );
+        assert!(matches!(
+            &replayed.state[0],
+            Ok(RenderOp::PushString(str)) if str == "Node \"A\"\n\u{7f}"
+        ));
+        assert!(matches!(replayed.render[0], Ok(RenderOp::PushColor(color)) if color.b == 1.));
+        assert!(matches!(replayed.render[2], Ok(RenderOp::DrawLines)));
+    }
+
+    #[test]
+    fn records_decode_errors() {
+        let frame = Frame {
+            exec: vec![Err(DecodeError::UnsupportedCommand { index: 0 })],
+            state: Vec::new(),
+            render: vec![
+                Ok(RenderOp::DrawLines),
+                Err(DecodeError::InvalidPayload {
+                    index: 1,
+                    command: "PushColor",
+                }),
+            ],
+        };
+
+        let mut buffer = Vec::new();
+        writeln!(buffer, "{} {}", MAGIC, VERSION).unwrap();
+        write_frame(&mut buffer, &frame).unwrap();
+
+        let mut replay = Replay::read(buffer.as_slice()).unwrap();
+        let replayed = replay.next_frame().unwrap();
+
+        assert!(matches!(
+            replayed.exec[0],
+            Err(DecodeError::UnsupportedCommand { index: 0 })
+        ));
+        assert!(matches!(replayed.render[0], Ok(RenderOp::DrawLines)));
+        assert_eq!(
+            replayed.render[1].as_ref().err(),
+            Some(&DecodeError::InvalidPayload {
+                index: 1,
+                command: "PushColor",
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let stream = format!("{} {}\nframe\n", MAGIC, VERSION + 1);
+
+        assert!(matches!(
+            Replay::read(stream.as_bytes()),
+            Err(ReplayError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_non_finite_payloads() {
+        let ops = [
+            "exec push_float32 NaN",
+            "state push_vec2f inf 0",
+            "render push_float32 -inf",
+        ];
+
+        for op in &ops {
+            let stream = format!("{} {}\nframe\n{}\n", MAGIC, VERSION, op);
+
+            assert!(matches!(
+                Replay::read(stream.as_bytes()),
+                Err(ReplayError::Parse { line_number: 3, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn holds_on_last_frame() {
+        let stream = format!("{} {}\nframe\nexec push_int32 1\nframe\n", MAGIC, VERSION);
+        let mut replay = Replay::read(stream.as_bytes()).unwrap();
+
+        assert_eq!(replay.next_frame().unwrap().exec.len(), 1);
+        assert_eq!(replay.next_frame().unwrap().exec.len(), 0);
+        assert_eq!(replay.next_frame().unwrap().exec.len(), 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! Decides when the canvas has to be repainted.
+
+use std::time::Duration;
+
+use druid::TimerToken;
+
+/// How often the canvas is repainted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSchedule {
+    fps_cap: f64,
+    always_redraw: bool,
+}
+
+impl FrameSchedule {
+    /// `fps_cap` is the upper bound of frames painted per second, `always_redraw` repaints on
+    /// every tick even if nothing changed, for debugging the core.
+    ///
+    /// Returns `None` unless `fps_cap` is a positive finite number.
+    pub fn new(fps_cap: f64, always_redraw: bool) -> Option<Self> {
+        if fps_cap > 0. && fps_cap.is_finite() {
+            Some(FrameSchedule {
+                fps_cap,
+                always_redraw,
+            })
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn fps_cap(&self) -> f64 {
+        self.fps_cap
+    }
+
+    pub fn always_redraw(&self) -> bool {
+        self.always_redraw
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1. / self.fps_cap)
+    }
+}
+
+impl Default for FrameSchedule {
+    fn default() -> Self {
+        FrameSchedule {
+            fps_cap: 60.,
+            always_redraw: false,
+        }
+    }
+}
+
+/// Repaints on input, data changes and while the core animates.
+///
+/// A timer ticks at the FPS cap as long as there is something to paint and stops once the
+/// core settles, so an idle canvas costs nothing. Changes arriving between two ticks are
+/// coalesced into the next tick.
+pub struct FrameScheduler {
+    schedule: FrameSchedule,
+    timer_id: TimerToken,
+    dirty: bool,
+    animating: bool,
+}
+
+impl FrameScheduler {
+    pub fn new(schedule: FrameSchedule) -> Self {
+        FrameScheduler {
+            schedule,
+            timer_id: TimerToken::INVALID,
+            dirty: false,
+            animating: false,
+        }
+    }
+
+    /// Marks the canvas as changed.
+    ///
+    /// Returns `true` if it should be painted right away; otherwise the running timer paints
+    /// it on the next tick. `request_timer` is called to start ticking when the timer is idle.
+    pub fn wake(&mut self, request_timer: impl FnOnce(Duration) -> TimerToken) -> bool {
+        if self.timer_id != TimerToken::INVALID {
+            self.dirty = true;
+            return false;
+        }
+
+        self.dirty = false;
+        self.timer_id = request_timer(self.schedule.frame_interval());
+        true
+    }
+
+    /// Handles a timer event, returns `true` if the canvas should be painted.
+    pub fn tick(
+        &mut self,
+        id: TimerToken,
+        request_timer: impl FnOnce(Duration) -> TimerToken,
+    ) -> bool {
+        if id != self.timer_id {
+            return false;
+        }
+
+        if self.dirty || self.animating || self.schedule.always_redraw {
+            self.dirty = false;
+            self.timer_id = request_timer(self.schedule.frame_interval());
+            true
+        }
+        else {
+            self.timer_id = TimerToken::INVALID;
+            false
+        }
+    }
+
+    /// Records whether the painted frame asked for another one.
+    pub fn frame_painted(&mut self, animating: bool) {
+        self.animating = animating;
+    }
+
+    /// Returns `true` if the last painted frame asked for another one.
+    pub fn is_animating(&self) -> bool {
+        self.animating
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler() -> FrameScheduler {
+        FrameScheduler::new(FrameSchedule::default())
+    }
+
+    #[test]
+    fn paints_right_away_when_idle() {
+        let mut scheduler = scheduler();
+        let timer = TimerToken::next();
+
+        assert!(scheduler.wake(|_| timer));
+        assert!(!scheduler.wake(|_| panic!("the timer is already running")));
+
+        // The change made while the timer was running is painted on the next tick.
+        let next = TimerToken::next();
+        assert!(scheduler.tick(timer, |_| next));
+        assert!(!scheduler.tick(next, |_| panic!("nothing changed")));
+
+        // The timer stopped, so the next change starts it again.
+        assert!(scheduler.wake(|_| TimerToken::next()));
+    }
+
+    #[test]
+    fn keeps_ticking_while_animating() {
+        let mut scheduler = scheduler();
+        let mut timer = TimerToken::next();
+
+        assert!(scheduler.wake(|_| timer));
+        scheduler.frame_painted(true);
+
+        for _ in 0..3 {
+            let next = TimerToken::next();
+            assert!(scheduler.tick(timer, |_| next));
+            scheduler.frame_painted(true);
+            timer = next;
+        }
+
+        scheduler.frame_painted(false);
+        assert!(!scheduler.tick(timer, |_| panic!("the animation ended")));
+    }
+
+    #[test]
+    fn rejects_invalid_fps_caps() {
+        assert!(FrameSchedule::new(0., false).is_none());
+        assert!(FrameSchedule::new(-30., false).is_none());
+        assert!(FrameSchedule::new(f64::NAN, false).is_none());
+        assert!(FrameSchedule::new(f64::INFINITY, false).is_none());
+        assert_eq!(
+            FrameSchedule::new(30., true).map(|schedule| schedule.frame_interval()),
+            Some(Duration::from_secs_f64(1. / 30.))
+        );
+    }
+
+    #[test]
+    fn ignores_stale_timers() {
+        let mut scheduler = scheduler();
+        let timer = TimerToken::next();
+
+        assert!(scheduler.wake(|_| timer));
+        assert!(!scheduler.tick(TimerToken::next(), |_| panic!("not our timer")));
+        assert!(!scheduler.tick(timer, |_| panic!("nothing changed")));
+    }
+}
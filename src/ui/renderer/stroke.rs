@@ -0,0 +1,62 @@
+//! The stroke style uniform of the stroked primitives.
+
+use druid::piet::{self, LineCap, LineJoin};
+
+pub const DEFAULT_STROKE_WIDTH: f64 = 1.;
+
+/// Width and style of the outlines of lines, paths, arcs and stroked shapes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub width: f64,
+    /// Lengths of the alternating dashes and gaps, solid if empty.
+    pub dash: Vec<f64>,
+    pub dash_offset: f64,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl Stroke {
+    pub fn is_solid(&self) -> bool {
+        self.dash.is_empty()
+    }
+
+    pub fn style(&self) -> piet::StrokeStyle {
+        let mut style = piet::StrokeStyle::new();
+        style.set_line_cap(self.cap);
+        style.set_line_join(self.join);
+
+        if !self.is_solid() {
+            style.set_dash(self.dash.clone(), self.dash_offset);
+        }
+
+        style
+    }
+}
+
+impl Default for Stroke {
+    fn default() -> Self {
+        Stroke {
+            width: DEFAULT_STROKE_WIDTH,
+            dash: Vec::new(),
+            dash_offset: 0.,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+        }
+    }
+}
+
+pub fn line_cap_from_i32(value: i32) -> LineCap {
+    match value {
+        1 => LineCap::Round,
+        2 => LineCap::Square,
+        _ => LineCap::Butt,
+    }
+}
+
+pub fn line_join_from_i32(value: i32) -> LineJoin {
+    match value {
+        1 => LineJoin::Round,
+        2 => LineJoin::Bevel,
+        _ => LineJoin::Miter,
+    }
+}
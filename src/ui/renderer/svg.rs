@@ -0,0 +1,568 @@
+//! A render backend that writes a standalone SVG document.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::iter;
+use std::ops::Range;
+use std::path::Path;
+
+use druid::kurbo::{Affine, Circle, Shape};
+use druid::piet::{LineCap, LineJoin};
+use druid::{Color, Point, Rect, Size};
+
+use super::backend::RenderBackend;
+use super::images::Image;
+use super::stroke::Stroke;
+use super::text::{HorizontalAlignment, TextMetrics, TextStyle, VerticalAlignment};
+
+/// Average advance of a glyph relative to the font size, used to estimate text sizes.
+const GLYPH_ADVANCE: f64 = 0.6;
+const LINE_HEIGHT: f64 = 1.2;
+/// Estimated height of a line above its baseline relative to the font size.
+const ASCENT: f64 = 0.9;
+
+/// Maximum distance of the flattened curves from the exact shapes.
+const PATH_TOLERANCE: f64 = 0.1;
+
+/// Collects the primitives of a frame into an SVG document.
+///
+/// Text is kept as `<text>` elements so the exported document stays searchable.
+pub struct SvgBackend {
+    size: Size,
+    defs: String,
+    body: String,
+    saved_groups: Vec<usize>,
+    open_groups: usize,
+    next_clip_id: usize,
+    next_gradient_id: usize,
+}
+
+impl SvgBackend {
+    pub fn new(size: Size) -> Self {
+        SvgBackend {
+            size,
+            defs: String::new(),
+            body: String::new(),
+            saved_groups: Vec::new(),
+            open_groups: 0,
+            next_clip_id: 0,
+            next_gradient_id: 0,
+        }
+    }
+
+    /// Closes all groups and returns the document.
+    pub fn finish(mut self) -> String {
+        while !self.saved_groups.is_empty() {
+            self.restore();
+        }
+        self.close_groups();
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\">\n<defs>\n{defs}</defs>\n{body}</svg>\n",
+            w = self.size.width,
+            h = self.size.height,
+            defs = self.defs,
+            body = self.body,
+        )
+    }
+
+    pub fn save_to_file(self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.finish())
+    }
+
+    fn open_group(&mut self, attributes: &str) {
+        let _ = writeln!(self.body, "<g {}>", attributes);
+        self.open_groups += 1;
+    }
+
+    fn close_groups(&mut self) {
+        for _ in 0..self.open_groups {
+            self.body.push_str("</g>\n");
+        }
+        self.open_groups = 0;
+    }
+}
+
+impl RenderBackend for SvgBackend {
+    fn fill_quad(&mut self, rect: Rect, color: &Color) {
+        let _ = writeln!(
+            self.body,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+            rect.x0,
+            rect.y0,
+            rect.width(),
+            rect.height(),
+            paint("fill", color),
+        );
+    }
+
+    fn fill_circle(&mut self, circle: Circle, color: &Color) {
+        let _ = writeln!(
+            self.body,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+            circle.center.x,
+            circle.center.y,
+            circle.radius,
+            paint("fill", color),
+        );
+    }
+
+    fn stroke_shape(&mut self, shape: &impl Shape, color: &Color, stroke: &Stroke) {
+        let _ = writeln!(
+            self.body,
+            "<path d=\"{}\" fill=\"none\" {} {}/>",
+            shape.to_path(PATH_TOLERANCE).to_svg(),
+            stroke_attributes(stroke),
+            paint("stroke", color),
+        );
+    }
+
+    fn fill_shape(&mut self, shape: &impl Shape, color: &Color) {
+        let _ = writeln!(
+            self.body,
+            "<path d=\"{}\" {}/>",
+            shape.to_path(PATH_TOLERANCE).to_svg(),
+            paint("fill", color),
+        );
+    }
+
+    fn fill_linear_gradient(
+        &mut self,
+        shape: &impl Shape,
+        start: (Point, &Color),
+        end: (Point, &Color),
+    ) {
+        let id = self.next_gradient_id;
+        self.next_gradient_id += 1;
+
+        let _ = writeln!(
+            self.defs,
+            "<linearGradient id=\"gradient{}\" gradientUnits=\"userSpaceOnUse\" \
+             x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\">{}{}</linearGradient>",
+            id,
+            start.0.x,
+            start.0.y,
+            end.0.x,
+            end.0.y,
+            gradient_stop(0., start.1),
+            gradient_stop(1., end.1),
+        );
+        let _ = writeln!(
+            self.body,
+            "<path d=\"{}\" fill=\"url(#gradient{})\"/>",
+            shape.to_path(PATH_TOLERANCE).to_svg(),
+            id,
+        );
+    }
+
+    fn draw_image(&mut self, image: &Image, dest: Rect, source: Option<Rect>, opacity: f64) {
+        let source =
+            source.unwrap_or_else(|| Rect::new(0., 0., image.width as f64, image.height as f64));
+
+        // A nested viewport crops the image to the source rect and scales it into `dest`.
+        let _ = writeln!(
+            self.body,
+            "<svg x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\" \
+             preserveAspectRatio=\"none\"><image width=\"{}\" height=\"{}\" opacity=\"{}\" \
+             href=\"data:{};base64,{}\"/></svg>",
+            dest.x0,
+            dest.y0,
+            dest.width(),
+            dest.height(),
+            source.x0,
+            source.y0,
+            source.width(),
+            source.height(),
+            image.width,
+            image.height,
+            opacity,
+            mime_type(&image.encoded),
+            base64(&image.encoded),
+        );
+    }
+
+    fn release_image(&mut self, _image: &Image) {}
+
+    /// The text is aligned by the viewer with `text-anchor` and `dominant-baseline` at the
+    /// position, so it doesn't depend on the estimated metrics of `measure_text`.
+    fn draw_text(&mut self, text: &str, position: Point, style: &TextStyle, color: &Color) {
+        let anchor = match style.horizontal_alignment {
+            HorizontalAlignment::Left => "start",
+            HorizontalAlignment::Center => "middle",
+            HorizontalAlignment::Right => "end",
+        };
+
+        let baseline = match style.vertical_alignment {
+            VerticalAlignment::Top => "text-before-edge",
+            VerticalAlignment::Middle => "central",
+            VerticalAlignment::Bottom => "text-after-edge",
+            VerticalAlignment::Baseline => "alphabetic",
+        };
+
+        let lines = line_ranges(text, style);
+
+        let content = match lines.as_slice() {
+            [_] => escape(text),
+            lines => {
+                // Moves the first line up so the whole block is aligned, not the first line.
+                let rows_above = match style.vertical_alignment {
+                    VerticalAlignment::Top | VerticalAlignment::Baseline => 0.,
+                    VerticalAlignment::Middle => (lines.len() - 1) as f64 / 2.,
+                    VerticalAlignment::Bottom => (lines.len() - 1) as f64,
+                };
+
+                lines
+                    .iter()
+                    .enumerate()
+                    .map(|(index, range)| {
+                        let rows = if index == 0 { -rows_above } else { 1. };
+
+                        format!(
+                            "<tspan x=\"{}\" dy=\"{}em\">{}</tspan>",
+                            position.x,
+                            rows * LINE_HEIGHT,
+                            escape(&text[range.clone()]),
+                        )
+                    })
+                    .collect()
+            }
+        };
+
+        let _ = writeln!(
+            self.body,
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" font-weight=\"{}\" \
+             font-style=\"{}\" text-anchor=\"{}\" dominant-baseline=\"{}\" \
+             xml:space=\"preserve\" {}>{}</text>",
+            position.x,
+            position.y,
+            escape(style.family.name()),
+            style.size,
+            style.weight,
+            if style.italic { "italic" } else { "normal" },
+            anchor,
+            baseline,
+            paint("fill", color),
+            content,
+        );
+    }
+
+    fn measure_text(&mut self, text: &str, style: &TextStyle, _color: &Color) -> TextMetrics {
+        let advance = style.size * GLYPH_ADVANCE;
+        let line_height = style.size * LINE_HEIGHT;
+        let lines = line_ranges(text, style);
+
+        let columns = lines
+            .iter()
+            .map(|range| text[range.clone()].chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let carets = text
+            .char_indices()
+            .map(|(index, _)| index)
+            .chain(iter::once(text.len()))
+            .map(|index| {
+                let line = lines
+                    .iter()
+                    .rposition(|range| range.start <= index)
+                    .unwrap_or(0);
+                let range = &lines[line];
+                let column = text[range.start..index.min(range.end)].chars().count();
+
+                Point::new(column as f64 * advance, line as f64 * line_height)
+            })
+            .collect();
+
+        TextMetrics {
+            size: Size::new(columns as f64 * advance, lines.len() as f64 * line_height),
+            baseline: style.size * ASCENT,
+            ascent: style.size * ASCENT,
+            descent: style.size * (LINE_HEIGHT - ASCENT),
+            line_count: lines.len(),
+            carets,
+        }
+    }
+
+    fn save(&mut self) {
+        self.saved_groups.push(self.open_groups);
+        self.open_groups = 0;
+    }
+
+    fn restore(&mut self) {
+        self.close_groups();
+        self.open_groups = self.saved_groups.pop().unwrap_or(0);
+    }
+
+    fn clip(&mut self, shape: &impl Shape) {
+        let id = self.next_clip_id;
+        self.next_clip_id += 1;
+
+        let _ = writeln!(
+            self.defs,
+            "<clipPath id=\"clip{}\"><path d=\"{}\"/></clipPath>",
+            id,
+            shape.to_path(PATH_TOLERANCE).to_svg(),
+        );
+        self.open_group(&format!("clip-path=\"url(#clip{})\"", id));
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        let [a, b, c, d, e, f] = transform.as_coeffs();
+        self.open_group(&format!(
+            "transform=\"matrix({} {} {} {} {} {})\"",
+            a, b, c, d, e, f
+        ));
+    }
+}
+
+fn paint(attribute: &str, color: &Color) -> String {
+    let rgba = color.as_rgba_u32();
+
+    format!(
+        "{attr}=\"#{:06x}\" {attr}-opacity=\"{}\"",
+        rgba >> 8,
+        (rgba & 0xFF) as f64 / 255.,
+        attr = attribute,
+    )
+}
+
+fn gradient_stop(offset: f64, color: &Color) -> String {
+    let rgba = color.as_rgba_u32();
+
+    format!(
+        "<stop offset=\"{}\" stop-color=\"#{:06x}\" stop-opacity=\"{}\"/>",
+        offset,
+        rgba >> 8,
+        (rgba & 0xFF) as f64 / 255.,
+    )
+}
+
+fn mime_type(encoded: &[u8]) -> &'static str {
+    match image::guess_format(encoded) {
+        Ok(image::ImageFormat::Png) => "image/png",
+        Ok(image::ImageFormat::Jpeg) => "image/jpeg",
+        Ok(image::ImageFormat::Gif) => "image/gif",
+        Ok(image::ImageFormat::Bmp) => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                result.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            }
+            else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+fn stroke_attributes(stroke: &Stroke) -> String {
+    let cap = match stroke.cap {
+        LineCap::Butt => "butt",
+        LineCap::Round => "round",
+        LineCap::Square => "square",
+    };
+
+    let join = match stroke.join {
+        LineJoin::Miter => "miter",
+        LineJoin::Round => "round",
+        LineJoin::Bevel => "bevel",
+    };
+
+    let mut attributes = format!(
+        "stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\"",
+        stroke.width, cap, join
+    );
+
+    if !stroke.is_solid() {
+        let dash: Vec<String> = stroke
+            .dash
+            .iter()
+            .map(|length| length.to_string())
+            .collect();
+        let _ = write!(
+            attributes,
+            " stroke-dasharray=\"{}\" stroke-dashoffset=\"{}\"",
+            dash.join(" "),
+            stroke.dash_offset
+        );
+    }
+
+    attributes
+}
+
+/// Splits the text into lines at newlines and, with a max width, wraps them after spaces.
+///
+/// Returns the byte ranges of the lines without the newlines.
+fn line_ranges(text: &str, style: &TextStyle) -> Vec<Range<usize>> {
+    let max_columns = style
+        .max_width
+        .map(|width| ((width / (style.size * GLYPH_ADVANCE)).floor() as usize).max(1));
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+
+    for paragraph in text.split('\n') {
+        match max_columns {
+            Some(max_columns) => wrap(paragraph, start, max_columns, &mut lines),
+            None => lines.push(start..start + paragraph.len()),
+        }
+
+        start += paragraph.len() + 1;
+    }
+
+    lines
+}
+
+fn wrap(paragraph: &str, offset: usize, max_columns: usize, lines: &mut Vec<Range<usize>>) {
+    let mut line_start = 0;
+    let mut columns = 0;
+    let mut last_break = None;
+
+    for (index, ch) in paragraph.char_indices() {
+        if columns == max_columns {
+            // Break after the last space of the line, or in the middle of a word that
+            // doesn't fit at all.
+            let break_at = last_break.filter(|at| *at > line_start).unwrap_or(index);
+
+            lines.push(offset + line_start..offset + break_at);
+            columns = paragraph[break_at..index].chars().count();
+            line_start = break_at;
+            last_break = None;
+        }
+
+        columns += 1;
+
+        if ch == ' ' {
+            last_break = Some(index + 1);
+        }
+    }
+
+    lines.push(offset + line_start..offset + paragraph.len());
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            ch => result.push(ch),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_stays_searchable() {
+        let mut svg = SvgBackend::new(Size::new(100., 50.));
+        svg.save();
+        svg.transform(Affine::translate((10., 20.)));
+        svg.draw_text(
+            "a < b & c",
+            Point::new(1., 2.),
+            &TextStyle::default(),
+            &Color::rgb8(0xFF, 0x00, 0x00),
+        );
+        svg.restore();
+
+        let document = svg.finish();
+
+        assert!(document.contains(">a &lt; b &amp; c</text>"));
+        assert!(document.contains("fill=\"#ff0000\""));
+        assert!(document.contains("transform=\"matrix(1 0 0 1 10 20)\""));
+        assert_eq!(
+            document.matches("<g ").count(),
+            document.matches("</g>").count()
+        );
+    }
+
+    #[test]
+    fn aligns_text_in_the_viewer() {
+        let mut svg = SvgBackend::new(Size::new(100., 50.));
+        let style = TextStyle {
+            horizontal_alignment: HorizontalAlignment::Center,
+            vertical_alignment: VerticalAlignment::Bottom,
+            ..TextStyle::default()
+        };
+
+        svg.draw_text(
+            "one\ntwo\nthree",
+            Point::new(50., 40.),
+            &style,
+            &Color::BLACK,
+        );
+
+        let document = svg.finish();
+
+        assert!(document.contains("x=\"50\" y=\"40\""));
+        let line = |rows: f64, text: &str| {
+            format!(
+                "<tspan x=\"50\" dy=\"{}em\">{}</tspan>",
+                rows * LINE_HEIGHT,
+                text
+            )
+        };
+
+        assert!(document.contains("text-anchor=\"middle\" dominant-baseline=\"text-after-edge\""));
+        assert!(document.contains(&line(-2., "one")));
+        assert!(document.contains(&line(1., "two")));
+    }
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        let mut svg = SvgBackend::new(Size::new(100., 50.));
+        let style = TextStyle {
+            // Ten glyphs of the default font size.
+            max_width: Some(75.),
+            ..TextStyle::default()
+        };
+
+        let metrics = svg.measure_text("one two three\nfour", &style, &Color::BLACK);
+        let advance = style.size * GLYPH_ADVANCE;
+        let line_height = style.size * LINE_HEIGHT;
+
+        assert_eq!(metrics.line_count, 3);
+        assert_eq!(
+            metrics.carets.len(),
+            "one two three\nfour".chars().count() + 1
+        );
+        assert_eq!(metrics.carets[8], Point::new(0., line_height));
+        assert_eq!(metrics.carets[14], Point::new(0., 2. * line_height));
+        assert_eq!(metrics.size.width, 8. * advance);
+    }
+}
@@ -0,0 +1,149 @@
+//! Text attributes of the `DrawText` command.
+
+use druid::{Point, Size};
+
+pub const DEFAULT_FONT_SIZE: f64 = 12.;
+pub const DEFAULT_FONT_WEIGHT: u16 = 400;
+
+/// The font family of a string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FontFamily {
+    SystemUi,
+    SansSerif,
+    Serif,
+    Monospace,
+    /// A family installed on the system, falls back to the system UI font if it's missing.
+    Named(String),
+}
+
+impl FontFamily {
+    /// Parses a family name, the CSS generic names select the generic families.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "" | "system-ui" => FontFamily::SystemUi,
+            "sans-serif" => FontFamily::SansSerif,
+            "serif" => FontFamily::Serif,
+            "monospace" => FontFamily::Monospace,
+            name => FontFamily::Named(name.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            FontFamily::SystemUi => "system-ui",
+            FontFamily::SansSerif => "sans-serif",
+            FontFamily::Serif => "serif",
+            FontFamily::Monospace => "monospace",
+            FontFamily::Named(name) => name,
+        }
+    }
+}
+
+/// Which point of the text box is placed at the text position horizontally, also how the
+/// lines of a multi-line string are aligned to each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Which point of the text box is placed at the text position vertically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerticalAlignment {
+    Top,
+    Middle,
+    Bottom,
+    /// The baseline of the first line.
+    Baseline,
+}
+
+impl HorizontalAlignment {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => HorizontalAlignment::Center,
+            2 => HorizontalAlignment::Right,
+            _ => HorizontalAlignment::Left,
+        }
+    }
+}
+
+impl VerticalAlignment {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => VerticalAlignment::Middle,
+            2 => VerticalAlignment::Bottom,
+            3 => VerticalAlignment::Baseline,
+            _ => VerticalAlignment::Top,
+        }
+    }
+}
+
+/// Font and alignment of a string, shared by the measurement and the render pass so the
+/// reported sizes match what is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub family: FontFamily,
+    pub size: f64,
+    /// The CSS weight, from 100 (thin) to 900 (black).
+    pub weight: u16,
+    pub italic: bool,
+    pub horizontal_alignment: HorizontalAlignment,
+    pub vertical_alignment: VerticalAlignment,
+    /// Lines longer than this are wrapped at word boundaries.
+    pub max_width: Option<f64>,
+}
+
+impl TextStyle {
+    /// Returns the top left corner of a text box of the given size and first baseline
+    /// so that its alignment point lands on `position`.
+    pub fn origin(&self, position: Point, size: Size, baseline: f64) -> Point {
+        let x = match self.horizontal_alignment {
+            HorizontalAlignment::Left => position.x,
+            HorizontalAlignment::Center => position.x - size.width / 2.,
+            HorizontalAlignment::Right => position.x - size.width,
+        };
+
+        let y = match self.vertical_alignment {
+            VerticalAlignment::Top => position.y,
+            VerticalAlignment::Middle => position.y - size.height / 2.,
+            VerticalAlignment::Bottom => position.y - size.height,
+            VerticalAlignment::Baseline => position.y - baseline,
+        };
+
+        Point::new(x, y)
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            family: FontFamily::SystemUi,
+            size: DEFAULT_FONT_SIZE,
+            weight: DEFAULT_FONT_WEIGHT,
+            italic: false,
+            horizontal_alignment: HorizontalAlignment::Left,
+            vertical_alignment: VerticalAlignment::Top,
+            max_width: None,
+        }
+    }
+}
+
+/// Measurements of a string, reported back to the core by the measurement pass.
+///
+/// All distances are in the coordinates of the text box, with the origin at its top
+/// left corner.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    pub size: Size,
+    /// Distance from the top of the text box to the baseline of the first line.
+    pub baseline: f64,
+    /// Height of the font above the baseline.
+    pub ascent: f64,
+    /// Depth of the font below the baseline.
+    pub descent: f64,
+    pub line_count: usize,
+    /// The top of the caret in front of every char and after the last one, i.e.
+    /// `text.chars().count() + 1` positions.
+    pub carets: Vec<Point>,
+}
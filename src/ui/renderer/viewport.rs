@@ -0,0 +1,38 @@
+//! The part of the window the canvas occupies, as reported to the core.
+
+use druid::{Point, Size};
+
+use tech_paws_core as core;
+
+/// Size, position and pixel density of the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// The size in device independent pixels.
+    pub size: Size,
+    /// The top left corner in window coordinates.
+    pub origin: Point,
+    /// The number of device pixels per device independent pixel.
+    pub scale: f64,
+}
+
+impl Viewport {
+    pub fn new(size: Size, origin: Point, scale: f64) -> Self {
+        Viewport {
+            size,
+            origin,
+            scale,
+        }
+    }
+
+    /// Sends the viewport to the core, which lays out in device independent pixels.
+    ///
+    /// `push_set_view_port_request_command` replaces the size only request of the core, the
+    /// origin comes from `window_origin` of the druid fork.
+    pub fn push_to_core(&self) {
+        core::push_set_view_port_request_command(core::commands::ViewPort {
+            size: core::commands::Vec2i::new(self.size.width as i32, self.size.height as i32),
+            origin: core::commands::Vec2f::new(self.origin.x as f32, self.origin.y as f32),
+            scale_factor: self.scale as f32,
+        });
+    }
+}
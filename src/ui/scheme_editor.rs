@@ -21,8 +21,8 @@ impl SchemeEditorState {
     }
 }
 
-pub fn build_ui() -> impl Widget<SchemeEditorState> {
-    Renderer::new()
+pub fn build_ui(renderer: Renderer<SchemeEditorState>) -> impl Widget<SchemeEditorState> {
+    renderer
 }
 
 fn _demo_build_ui() -> impl Widget<SchemeEditorState> {